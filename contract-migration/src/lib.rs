@@ -0,0 +1,122 @@
+use thiserror::Error;
+
+pub use semver::Version;
+
+// The version checks every contract in this repository runs in its migrate entry point.
+//
+// 0.1.0 of the zk and same_chain verifier contracts and of the cross_chain verifier all stored the
+// cw2 name "crates.io:verifier-contract", and both business contracts "crates.io:business-contract".
+// A contract cannot tell from that name alone whether it is being migrated over itself or over one
+// of the others, so a legacy name is only accepted when the migrate message repeats it and the
+// stored state has the layout this contract's 0.1.0 left.
+//
+// The contracts are on different cosmwasm-std versions, so this crate depends on neither
+// cosmwasm-std nor cw2. Each migrate entry point reads the stored cw2 version, checks it here and
+// stores its own version afterwards.
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("{0}")]
+    SemVer(#[from] semver::Error),
+
+    #[error("Cannot migrate from {previous_contract}")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("{previous_contract} was stored by several contracts, pass it as legacy_contract to migrate from it")]
+    UnconfirmedLegacyContract { previous_contract: String },
+
+    #[error("Stored state is not that of a {previous_contract} deployment of this contract")]
+    LegacyLayoutMismatch { previous_contract: String },
+
+    #[error("Cannot migrate from version {previous_version} to the older {version}")]
+    CannotDowngrade { previous_version: String, version: String },
+}
+
+pub struct Migration<'a> {
+    // cw2 name and version this build stores
+    pub contract: &'a str,
+    pub version: &'a str,
+    // cw2 name 0.1.0 of this contract stored, shared with other contracts
+    pub legacy_contract: &'a str,
+}
+
+impl Migration<'_> {
+    // Refuses other contracts and newer versions of this one. Returns the version migrated from,
+    // which storage layout migrations are keyed on. previous_contract and previous_version are the
+    // stored cw2 ones, legacy_contract is the migrate message's confirmation of a shared 0.1.0 name
+    // and legacy_layout tells whether the stored state looks like what 0.1.0 of this contract left
+    // behind. It is only called for a confirmed legacy name.
+    pub fn check(
+        &self,
+        previous_contract: &str,
+        previous_version: &str,
+        legacy_contract: Option<&str>,
+        legacy_layout: impl FnOnce() -> bool,
+    ) -> Result<Version, MigrationError> {
+        if previous_contract != self.contract {
+            let previous_contract = previous_contract.to_string();
+            if previous_contract != self.legacy_contract {
+                return Err(MigrationError::CannotMigrate { previous_contract });
+            }
+            if legacy_contract != Some(self.legacy_contract) {
+                return Err(MigrationError::UnconfirmedLegacyContract { previous_contract });
+            }
+            if !legacy_layout() {
+                return Err(MigrationError::LegacyLayoutMismatch { previous_contract });
+            }
+        }
+
+        let parsed_previous_version: Version = previous_version.parse()?;
+        let version: Version = self.version.parse()?;
+        if parsed_previous_version > version {
+            return Err(MigrationError::CannotDowngrade {
+                previous_version: previous_version.to_string(),
+                version: self.version.to_string(),
+            });
+        }
+        Ok(parsed_previous_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATION: Migration = Migration {
+        contract: "crates.io:zk-verifier-contract",
+        version: "0.2.0",
+        legacy_contract: "crates.io:verifier-contract",
+    };
+
+    #[test]
+    fn test_migration_check() {
+        let legacy_layout = || -> bool { panic!("only a confirmed legacy name checks the layout") };
+
+        // This contract at the same or an older version
+        let version = MIGRATION.check("crates.io:zk-verifier-contract", "0.2.0", None, legacy_layout).unwrap();
+        assert_eq!(version, Version::new(0, 2, 0));
+        let version = MIGRATION.check("crates.io:zk-verifier-contract", "0.1.3", None, legacy_layout).unwrap();
+        assert_eq!(version, Version::new(0, 1, 3));
+
+        // The shared 0.1.0 name has to be confirmed and the stored state has to match
+        let err = MIGRATION.check("crates.io:verifier-contract", "0.1.0", None, legacy_layout).unwrap_err();
+        assert!(matches!(err, MigrationError::UnconfirmedLegacyContract { .. }));
+        let err = MIGRATION
+            .check("crates.io:verifier-contract", "0.1.0", Some("crates.io:business-contract"), legacy_layout)
+            .unwrap_err();
+        assert!(matches!(err, MigrationError::UnconfirmedLegacyContract { .. }));
+        let legacy_contract = Some("crates.io:verifier-contract");
+        let err = MIGRATION.check("crates.io:verifier-contract", "0.1.0", legacy_contract, || false).unwrap_err();
+        assert!(matches!(err, MigrationError::LegacyLayoutMismatch { .. }));
+        let version = MIGRATION.check("crates.io:verifier-contract", "0.1.0", legacy_contract, || true).unwrap();
+        assert_eq!(version, Version::new(0, 1, 0));
+
+        // Other contracts, newer versions and versions that are not semver are refused
+        let err = MIGRATION.check("crates.io:business-contract", "0.1.0", legacy_contract, legacy_layout).unwrap_err();
+        assert!(matches!(err, MigrationError::CannotMigrate { .. }));
+        let err = MIGRATION.check("crates.io:zk-verifier-contract", "1.0.0", None, legacy_layout).unwrap_err();
+        assert!(matches!(err, MigrationError::CannotDowngrade { .. }));
+        let err = MIGRATION.check("crates.io:zk-verifier-contract", "not a version", None, legacy_layout).unwrap_err();
+        assert!(matches!(err, MigrationError::SemVer(_)));
+    }
+}
@@ -5,6 +5,14 @@ use crate::ics999::{Action};
 #[cw_serde]
 pub struct InstantiateMsg {}
 
+#[cw_serde]
+pub struct MigrateMsg {
+    /// Set to "crates.io:business-contract" to migrate a 0.1.0 deployment, whose cw2 name other
+    /// contracts stored as well
+    #[serde(default)]
+    pub legacy_contract: Option<String>,
+}
+
 #[cw_serde]
 pub enum ExecuteMsg {
     /// Send a packet consisting of a series of actions
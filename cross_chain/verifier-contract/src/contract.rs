@@ -2,17 +2,29 @@
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
     Binary, Deps, DepsMut, Env, MessageInfo, Response,
-    StdResult, to_binary,
+    StdResult, Storage, to_binary,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use contract_migration::Migration;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, QueryRandomNumResponse};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, QueryRandomNumResponse};
 use crate::host::{handle};
 
 // version info for migration info
-const CONTRACT_NAME: &str = "crates.io:verifier-contract";
+const CONTRACT_NAME: &str = "crates.io:cross-chain-verifier-contract";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+// 0.1.0 stored "crates.io:verifier-contract", which the zk and same_chain verifier contracts used too.
+// Those kept their config under "state", a key this contract never writes
+const MIGRATION: Migration = Migration {
+    contract: CONTRACT_NAME,
+    version: CONTRACT_VERSION,
+    legacy_contract: "crates.io:verifier-contract",
+};
+
+fn legacy_layout(storage: &dyn Storage) -> bool {
+    storage.get(b"state").is_none()
+}
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -60,5 +72,45 @@ pub fn return_random_num() -> StdResult<Binary> {
     to_binary(&QueryRandomNumResponse { result: 2356 })
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    // No storage layout changes yet, migrations of later layouts go here keyed on previous_version
+    let previous = get_contract_version(deps.storage)?;
+    let previous_version =
+        MIGRATION.check(&previous.contract, &previous.version, msg.legacy_contract.as_deref(), || legacy_layout(deps.storage))?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("previous_version", previous_version.to_string())
+        .add_attribute("version", CONTRACT_VERSION))
+}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use contract_migration::MigrationError;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+
+    use super::*;
+
+    const LEGACY_CONTRACT_NAME: &str = "crates.io:verifier-contract";
+
+    #[test]
+    fn test_migrate_legacy_layout() {
+        // The shared name over the empty state 0.1.0 of this contract left
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        cw2::set_contract_version(&mut deps.storage, LEGACY_CONTRACT_NAME, "0.1.0").unwrap();
+        let legacy_msg = MigrateMsg { legacy_contract: Some(LEGACY_CONTRACT_NAME.to_string()) };
+        let res = migrate(deps.as_mut(), env.clone(), legacy_msg.clone()).unwrap();
+        assert_eq!(res.attributes[1].value, "0.1.0");
+        assert_eq!(cw2::get_contract_version(&deps.storage).unwrap().contract, CONTRACT_NAME);
+
+        // The shared name over a config the other 0.1.0 contracts stored under "state"
+        let mut deps = mock_dependencies();
+        deps.storage.set(b"state", br#"{"admin_address":"addr1"}"#);
+        cw2::set_contract_version(&mut deps.storage, LEGACY_CONTRACT_NAME, "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), env, legacy_msg).unwrap_err();
+        assert!(matches!(err, ContractError::Migration(MigrationError::LegacyLayoutMismatch { .. })));
+    }
+}
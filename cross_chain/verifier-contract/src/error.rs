@@ -1,5 +1,6 @@
 use cosmwasm_std::{IbcOrder, Instantiate2AddressError, OverflowError, StdError};
 use cw_utils::{ParseReplyError, PaymentError};
+use contract_migration::MigrationError;
 
 use thiserror::Error;
 
@@ -20,6 +21,9 @@ pub enum ContractError {
     #[error(transparent)]
     ParseReply(#[from] ParseReplyError),
 
+    #[error(transparent)]
+    Migration(#[from] MigrationError),
+
     #[error("action queue cannot be empty")]
     EmptyActionQueue,
 
@@ -30,6 +30,15 @@ pub struct CallVerifyTruthMethodResponse {
     pub result: bool,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {
+    // Set to "crates.io:business-contract" to migrate a 0.1.0 deployment, whose cw2 name other contracts
+    // stored as well
+    #[serde(default)]
+    pub legacy_contract: Option<String>,
+}
+
 // Structs for External contracts
 
 #[derive(Serialize, Deserialize)]
@@ -1,14 +1,27 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, to_binary};
-use cw2::set_contract_version;
+use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Storage, to_binary};
+use cw2::{get_contract_version, set_contract_version};
+use contract_migration::Migration;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, VerifyTruthResponse};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, VerifyTruthResponse};
 use crate::state::{Config, CONFIG};
 
-const CONTRACT_NAME: &str = "crates.io:verifier-contract";
+const CONTRACT_NAME: &str = "crates.io:same-chain-verifier-contract";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION"); 
+// 0.1.0 stored "crates.io:verifier-contract", which the zk and cross_chain verifier contracts used too.
+// The cross_chain one left no config under "state". The zk one did, so migrate_msg.legacy_contract
+// is what confirms the deployment is this contract
+const MIGRATION: Migration = Migration {
+    contract: CONTRACT_NAME,
+    version: CONTRACT_VERSION,
+    legacy_contract: "crates.io:verifier-contract",
+};
+
+fn legacy_layout(storage: &dyn Storage) -> bool {
+    matches!(CONFIG.may_load(storage), Ok(Some(_)))
+}
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -58,11 +71,27 @@ fn query_verify_truth(a: u64, b: u64, sum: u64) -> StdResult<Binary> {
     to_binary(&VerifyTruthResponse { valid: is_valid_sum })
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    // No storage layout changes yet. Migrations of later layouts go here, keyed on previous_version
+    let previous = get_contract_version(deps.storage)?;
+    let previous_version =
+        MIGRATION.check(&previous.contract, &previous.version, msg.legacy_contract.as_deref(), || legacy_layout(deps.storage))?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("previous_version", previous_version.to_string())
+        .add_attribute("version", CONTRACT_VERSION))
+}
+
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::{testing::{mock_dependencies, mock_env, mock_info}, from_binary};
-    use crate::{msg::{InstantiateMsg, QueryMsg, VerifyTruthResponse}};
-    use super::{instantiate, query};
+    use contract_migration::MigrationError;
+    use crate::ContractError;
+    use crate::{msg::{InstantiateMsg, MigrateMsg, QueryMsg, VerifyTruthResponse}};
+    use super::{instantiate, migrate, query, CONTRACT_NAME};
 
     #[test]
     fn test_verify_truth_invalid_case() {
@@ -107,4 +136,27 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_migrate_legacy_layout() {
+        // The shared name over the config 0.1.0 of this contract left
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let info = mock_info("addr1", &[]);
+        let msg = InstantiateMsg{
+            admin_address: "addr1".to_string()
+        };
+        let _ = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+        cw2::set_contract_version(&mut deps.storage, "crates.io:verifier-contract", "0.1.0").unwrap();
+        let legacy_msg = MigrateMsg { legacy_contract: Some("crates.io:verifier-contract".to_string()) };
+        let res = migrate(deps.as_mut(), env.clone(), legacy_msg.clone()).unwrap();
+        assert_eq!(res.attributes[1].value, "0.1.0");
+        assert_eq!(cw2::get_contract_version(&deps.storage).unwrap().contract, CONTRACT_NAME);
+
+        // The shared name over the state the cross_chain verifier leaves, which has no config
+        let mut deps = mock_dependencies();
+        cw2::set_contract_version(&mut deps.storage, "crates.io:verifier-contract", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), env, legacy_msg).unwrap_err();
+        assert!(matches!(err, ContractError::Migration(MigrationError::LegacyLayoutMismatch { .. })));
+    }
 }
@@ -1,3 +1,4 @@
+use contract_migration::MigrationError;
 use cosmwasm_std::StdError;
 use thiserror::Error;
 
@@ -8,4 +9,7 @@ pub enum ContractError {
 
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
+
+    #[error("{0}")]
+    Migration(#[from] MigrationError),
 }
@@ -31,4 +31,9 @@ pub struct VerifyTruthResponse {
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum MigrateMsg {}
+pub struct MigrateMsg {
+    // Set to "crates.io:verifier-contract" to migrate a 0.1.0 deployment, whose cw2 name other contracts
+    // stored as well
+    #[serde(default)]
+    pub legacy_contract: Option<String>,
+}
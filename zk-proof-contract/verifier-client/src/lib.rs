@@ -0,0 +1,147 @@
+use bellman::groth16::{create_random_proof, generate_random_parameters, Parameters, Proof, VerifyingKey};
+use bellman::{Circuit, ConstraintSystem, SynthesisError};
+use bls12_381::{Bls12, Scalar};
+use cosmwasm_std::Binary;
+use ff::PrimeField;
+use rand_core::RngCore;
+use thiserror::Error;
+
+use verifier_contract::msg::{ExecuteMsg, QueryMsg};
+use verifier_contract::state::{Curve, NullifierSource, PointEncoding, ProofStr, PublicInput, VkeyStr};
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("{0}")]
+    Synthesis(#[from] SynthesisError),
+
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+}
+
+// c_i <- a_i * b_i for every pair, with each c_i as a public input. One pair is the original demo
+// circuit the contract used to hardcode the key of.
+pub struct Multiplier {
+    pub pairs: Vec<(Option<Scalar>, Option<Scalar>)>,
+}
+
+impl Multiplier {
+    // Shape of the circuit without a witness, for the setup
+    pub fn blank(num_pairs: usize) -> Self {
+        Multiplier { pairs: vec![(None, None); num_pairs] }
+    }
+
+    pub fn new(pairs: &[(Scalar, Scalar)]) -> Self {
+        Multiplier { pairs: pairs.iter().map(|(a, b)| (Some(*a), Some(*b))).collect() }
+    }
+
+    // The public inputs a proof of these pairs is verified against, in circuit order
+    pub fn products(pairs: &[(Scalar, Scalar)]) -> Vec<Scalar> {
+        pairs.iter().map(|(a, b)| a * b).collect()
+    }
+}
+
+impl Circuit<Scalar> for Multiplier {
+    fn synthesize<CS: ConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        for (i, (a_val, b_val)) in self.pairs.into_iter().enumerate() {
+            let a = cs.alloc(|| format!("a_{}", i), || a_val.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.alloc(|| format!("b_{}", i), || b_val.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = cs.alloc_input(
+                || format!("c_{}", i),
+                || Ok(a_val.ok_or(SynthesisError::AssignmentMissing)? * b_val.ok_or(SynthesisError::AssignmentMissing)?),
+            )?;
+            cs.enforce(|| format!("a_{} * b_{} = c_{}", i, i, i), |lc| lc + a, |lc| lc + b, |lc| lc + c);
+        }
+        Ok(())
+    }
+}
+
+// Groth16 parameters for a multiplier of num_pairs pairs. The randomness is toxic waste: whoever
+// knows it can prove anything, so only use this setup for circuits nobody else relies on.
+pub fn setup<R: RngCore>(num_pairs: usize, rng: &mut R) -> Result<Parameters<Bls12>, ClientError> {
+    Ok(generate_random_parameters::<Bls12, _, _>(Multiplier::blank(num_pairs), rng)?)
+}
+
+pub fn prove<R: RngCore>(
+    params: &Parameters<Bls12>,
+    pairs: &[(Scalar, Scalar)],
+    rng: &mut R,
+) -> Result<Proof<Bls12>, ClientError> {
+    Ok(create_random_proof(Multiplier::new(pairs), params, rng)?)
+}
+
+// The key as RegisterVerifyingKey takes it
+pub fn vkey_str(vk: &VerifyingKey<Bls12>, encoding: PointEncoding) -> VkeyStr {
+    let (g1, g2) = encoders(encoding);
+    VkeyStr {
+        curve: Curve::Bls12_381,
+        encoding,
+        alpha_1: g1(&vk.alpha_g1),
+        beta_2: g2(&vk.beta_g2),
+        gamma_2: g2(&vk.gamma_g2),
+        delta_2: g2(&vk.delta_g2),
+        ic: vk.ic.iter().map(g1).collect(),
+    }
+}
+
+// The proof in the format parse_proof reads
+pub fn proof_str(proof: &Proof<Bls12>, encoding: PointEncoding) -> ProofStr {
+    let (g1, g2) = encoders(encoding);
+    ProofStr {
+        encoding,
+        pi_a: g1(&proof.a),
+        pi_b: g2(&proof.b),
+        pi_c: g1(&proof.c),
+    }
+}
+
+// Public inputs as the canonical little endian encoding of each scalar
+pub fn public_inputs(values: &[Scalar]) -> Vec<PublicInput> {
+    values.iter().map(|value| PublicInput::HexLe(hex::encode(value.to_repr()))).collect()
+}
+
+pub fn register_verifying_key_msg(
+    circuit_id: &str,
+    vk: &VerifyingKey<Bls12>,
+    encoding: PointEncoding,
+    nullifier: Option<NullifierSource>,
+) -> ExecuteMsg {
+    ExecuteMsg::RegisterVerifyingKey {
+        circuit_id: circuit_id.to_string(),
+        vkey: vkey_str(vk, encoding),
+        nullifier,
+        grace_period: None,
+    }
+}
+
+// A VerifyProof query against the active key of the circuit
+pub fn verify_proof_msg(
+    circuit_id: &str,
+    proof: &Proof<Bls12>,
+    public_input_values: &[Scalar],
+    encoding: PointEncoding,
+) -> Result<QueryMsg, ClientError> {
+    Ok(QueryMsg::VerifyProof {
+        circuit_id: circuit_id.to_string(),
+        proof: serde_json::to_string(&proof_str(proof, encoding))?,
+        public_inputs: public_inputs(public_input_values),
+        key_version: None,
+        expected_vkey_hash: None,
+        named_inputs: None,
+    })
+}
+
+type G1Encoder = fn(&bls12_381::G1Affine) -> Binary;
+type G2Encoder = fn(&bls12_381::G2Affine) -> Binary;
+
+fn encoders(encoding: PointEncoding) -> (G1Encoder, G2Encoder) {
+    match encoding {
+        PointEncoding::Uncompressed => (
+            |point| Binary::from(point.to_uncompressed().as_ref()),
+            |point| Binary::from(point.to_uncompressed().as_ref()),
+        ),
+        PointEncoding::Compressed => (
+            |point| Binary::from(point.to_compressed().as_ref()),
+            |point| Binary::from(point.to_compressed().as_ref()),
+        ),
+    }
+}
@@ -0,0 +1,75 @@
+//! Compares one VerifyProofBatch query against N VerifyProof queries for the same circuit.
+//!
+//! Run with `cargo bench --bench batch_verify`. This measures native execution time of the
+//! query entry point only. Both paths are dominated by Miller loops and final exponentiations:
+//! N single checks cost N final exponentiations, a batch costs one plus a multi Miller loop over
+//! N + 3 pairs. For gas, benches/vm_gas.rs runs the same comparison in the CosmWasm VM.
+
+use bellman::groth16::Parameters;
+use bls12_381::{Bls12, Scalar};
+use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand_core::OsRng;
+
+use verifier_client::Multiplier;
+use verifier_contract::contract::{execute, instantiate, query};
+use verifier_contract::msg::{InstantiateMsg, QueryMsg};
+use verifier_contract::state::{PointEncoding, PublicInput};
+
+fn prove(params: &Parameters<Bls12>, a: u64, b: u64) -> (String, Vec<PublicInput>) {
+    let pairs = [(Scalar::from(a), Scalar::from(b))];
+    let proof = verifier_client::prove(params, &pairs, &mut OsRng).unwrap();
+    let proof_str = verifier_client::proof_str(&proof, PointEncoding::Uncompressed);
+    let public_inputs = verifier_client::public_inputs(&Multiplier::products(&pairs));
+    (serde_json::to_string(&proof_str).unwrap(), public_inputs)
+}
+
+fn bench_batch_verify(c: &mut Criterion) {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let admin = deps.api.addr_make("admin");
+    let msg = InstantiateMsg { admin_address: admin.to_string() };
+    instantiate(deps.as_mut(), env.clone(), message_info(&admin, &[]), msg).unwrap();
+
+    let params = verifier_client::setup(1, &mut OsRng).unwrap();
+    let register_msg =
+        verifier_client::register_verifying_key_msg("multiply", &params.vk, PointEncoding::Uncompressed, None);
+    execute(deps.as_mut(), env.clone(), message_info(&admin, &[]), register_msg).unwrap();
+
+    let mut group = c.benchmark_group("verify_groth16_bls12_381");
+    group.sample_size(10);
+    for n in [1u64, 4, 16, 32] {
+        let proofs: Vec<(String, Vec<PublicInput>)> = (1..=n).map(|a| prove(&params, a, 7)).collect();
+
+        group.bench_with_input(BenchmarkId::new("single", n), &proofs, |b, proofs| {
+            b.iter(|| {
+                for (proof, public_inputs) in proofs.iter() {
+                    let query_msg = QueryMsg::VerifyProof {
+                        circuit_id: "multiply".to_string(),
+                        proof: proof.clone(),
+                        public_inputs: public_inputs.clone(),
+                        key_version: None,
+                        expected_vkey_hash: None,
+                        named_inputs: None,
+                    };
+                    query(deps.as_ref(), env.clone(), query_msg).unwrap();
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("batch", n), &proofs, |b, proofs| {
+            b.iter(|| {
+                let query_msg = QueryMsg::VerifyProofBatch {
+                    circuit_id: "multiply".to_string(),
+                    proofs: proofs.clone(),
+                    key_version: None,
+                };
+                query(deps.as_ref(), env.clone(), query_msg).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_batch_verify);
+criterion_main!(benches);
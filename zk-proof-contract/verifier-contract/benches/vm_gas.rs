@@ -0,0 +1,324 @@
+//! Gas and time of Groth16 verification inside the CosmWasm VM, at growing public input counts.
+//!
+//! Build the contract first, then run the suite against the optimized wasm:
+//!
+//!     cargo wasm
+//!     cargo bench --bench vm_gas
+//!
+//! The wasm is read from VERIFIER_WASM if set, otherwise from
+//! target/wasm32-unknown-unknown/release/verifier_contract.wasm. Point VERIFIER_WASM at the
+//! optimizer output in artifacts/ to measure the code that gets uploaded.
+//!
+//! Prints a markdown table with one row per operation and input count, then one comparing a
+//! VerifyProofBatch of N proofs with N VerifyProof queries. Gas is the VM gas the instance reports,
+//! before wasmd converts it to SDK gas. Times are wall clock per call, averaged over RUNS calls,
+//! and include the VM's host calls such as storage reads.
+//!
+//! With VERIFIER_BASELINE_WASM set to a build of BASELINE_COMMIT, the last one that prepared the
+//! verifying key on every query, a third table compares VerifyProof there and here:
+//!
+//!     git worktree add /tmp/baseline f5402df
+//!     (cd /tmp/baseline/zk-proof-contract/verifier-contract && CARGO_TARGET_DIR=/tmp/baseline-target cargo wasm)
+//!     VERIFIER_BASELINE_WASM=/tmp/baseline-target/wasm32-unknown-unknown/release/verifier_contract.wasm \
+//!         cargo bench --bench vm_gas
+
+use std::time::{Duration, Instant};
+
+use bellman::groth16::Parameters;
+use bls12_381::{Bls12, Scalar};
+use cosmwasm_std::{ContractResult, Empty, QueryResponse, Response};
+use cosmwasm_vm::testing::{
+    execute, instantiate, mock_env, mock_info, mock_instance_with_options, query, MockApi, MockInstanceOptions,
+    MockQuerier, MockStorage,
+};
+use cosmwasm_vm::Instance;
+use rand_core::OsRng;
+use serde_json::{json, Value};
+
+use verifier_client::Multiplier;
+use verifier_contract::msg::{InstantiateMsg, QueryMsg, VerifyProofBatchResponse, VerifyProofResponse};
+use verifier_contract::state::{PointEncoding, PublicInput};
+
+const DEFAULT_WASM: &str = "target/wasm32-unknown-unknown/release/verifier_contract.wasm";
+const INPUT_COUNTS: [usize; 6] = [1, 2, 4, 8, 16, 32];
+const BATCH_SIZES: [usize; 6] = [1, 2, 4, 8, 16, 32];
+// Parent of the commit that stored prepared keys at registration. Its messages lack the fields
+// added since, and VerifyProof answered {"result": "Done"} or {"result": "Not Done"}
+const BASELINE_COMMIT: &str = "f5402df";
+const RUNS: u32 = 5;
+const GAS_LIMIT: u64 = 1_000_000_000_000_000;
+// BLS12-381 scalar field modulus. Public inputs are parsed after the proof, so a query with it
+// stops right before the pairing check
+const NON_CANONICAL_INPUT: &str =
+    "52435875175126190479447740508185965837690552500527637822603658699938581184513";
+
+type VmInstance = Instance<MockApi, MockStorage, MockQuerier>;
+
+struct Measurement {
+    operation: &'static str,
+    inputs: usize,
+    gas: u64,
+    time: Duration,
+}
+
+struct BatchMeasurement {
+    proofs: usize,
+    singles_gas: u64,
+    singles_time: Duration,
+    batch_gas: u64,
+    batch_time: Duration,
+}
+
+struct BaselineMeasurement {
+    inputs: usize,
+    baseline_gas: u64,
+    gas: u64,
+}
+
+fn main() {
+    let wasm_path = std::env::var("VERIFIER_WASM").unwrap_or_else(|_| DEFAULT_WASM.to_string());
+    let mut instance = new_instance(&wasm_path);
+    let mut baseline = std::env::var("VERIFIER_BASELINE_WASM").ok().map(|path| new_instance(&path));
+
+    let mut measurements = Vec::new();
+    let mut baseline_measurements = Vec::new();
+    for inputs in INPUT_COUNTS {
+        let params = verifier_client::setup(inputs, &mut OsRng).unwrap();
+        measure_registration(&mut instance, &params, inputs, &mut measurements);
+        let gas = measure_verification(&mut instance, &params, inputs, &mut measurements);
+        if let Some(baseline) = baseline.as_mut() {
+            let baseline_gas = measure_baseline(baseline, &params, inputs);
+            baseline_measurements.push(BaselineMeasurement { inputs, baseline_gas, gas });
+        }
+    }
+    let batch_measurements = measure_batches(&mut instance);
+
+    println!("| operation | public inputs | gas | time (ms) |");
+    println!("|---|---|---|---|");
+    for measurement in measurements.iter() {
+        println!(
+            "| {} | {} | {} | {:.2} |",
+            measurement.operation,
+            measurement.inputs,
+            measurement.gas,
+            measurement.time.as_secs_f64() * 1000.0,
+        );
+    }
+
+    println!();
+    println!("| proofs | N x VerifyProof gas | VerifyProofBatch gas | batch / singles | N x VerifyProof (ms) | VerifyProofBatch (ms) |");
+    println!("|---|---|---|---|---|---|");
+    for measurement in batch_measurements.iter() {
+        println!(
+            "| {} | {} | {} | {:.2} | {:.2} | {:.2} |",
+            measurement.proofs,
+            measurement.singles_gas,
+            measurement.batch_gas,
+            measurement.batch_gas as f64 / measurement.singles_gas as f64,
+            measurement.singles_time.as_secs_f64() * 1000.0,
+            measurement.batch_time.as_secs_f64() * 1000.0,
+        );
+    }
+
+    if !baseline_measurements.is_empty() {
+        println!();
+        println!("| public inputs | VerifyProof gas, key prepared per query ({}) | VerifyProof gas, stored prepared key | saved |", BASELINE_COMMIT);
+        println!("|---|---|---|---|");
+        for measurement in baseline_measurements.iter() {
+            println!(
+                "| {} | {} | {} | {:.0}% |",
+                measurement.inputs,
+                measurement.baseline_gas,
+                measurement.gas,
+                100.0 * (1.0 - measurement.gas as f64 / measurement.baseline_gas as f64),
+            );
+        }
+    }
+}
+
+// The VM's MockApi only accepts bech32 addresses
+fn admin() -> String {
+    MockApi::default().addr_make("admin")
+}
+
+fn new_instance(wasm_path: &str) -> VmInstance {
+    let wasm = std::fs::read(wasm_path)
+        .unwrap_or_else(|err| panic!("cannot read {} ({}), build it with `cargo wasm` first", wasm_path, err));
+    let options = MockInstanceOptions { gas_limit: GAS_LIMIT, ..Default::default() };
+    let mut instance = mock_instance_with_options(&wasm, options);
+    let msg = InstantiateMsg { admin_address: admin() };
+    let res: ContractResult<Response<Empty>> = instantiate(&mut instance, mock_env(), mock_info(&admin(), &[]), msg);
+    res.into_result().unwrap();
+    instance
+}
+
+// Key parsing, the subgroup checks and key preparation all happen when a key is registered.
+// Compressed keys add a square root per point to the parsing.
+fn measure_registration(
+    instance: &mut VmInstance,
+    params: &Parameters<Bls12>,
+    inputs: usize,
+    measurements: &mut Vec<Measurement>,
+) {
+    for (operation, encoding) in [
+        ("register key", PointEncoding::Uncompressed),
+        ("register key, compressed", PointEncoding::Compressed),
+    ] {
+        let (gas, time) = average(|run| {
+            // A new circuit every run, so each one stores its first key version
+            let circuit_id = format!("{}_{}_{:?}", inputs, run, encoding);
+            let msg = verifier_client::register_verifying_key_msg(&circuit_id, &params.vk, encoding, None);
+            gas_used(instance, |instance| {
+                let res: ContractResult<Response<Empty>> =
+                    execute(instance, mock_env(), mock_info(&admin(), &[]), msg);
+                res.into_result().unwrap();
+            })
+        });
+        measurements.push(Measurement { operation, inputs, gas, time });
+    }
+}
+
+// The pairing check is what a valid proof costs beyond one rejected with the last public input
+// out of range: both load the prepared key and decode the proof, only the valid one reaches the
+// input sum and the pairings.
+fn measure_verification(
+    instance: &mut VmInstance,
+    params: &Parameters<Bls12>,
+    inputs: usize,
+    measurements: &mut Vec<Measurement>,
+) -> u64 {
+    let circuit_id = format!("verify_{}", inputs);
+    let msg = verifier_client::register_verifying_key_msg(&circuit_id, &params.vk, PointEncoding::Uncompressed, None);
+    let res: ContractResult<Response<Empty>> = execute(instance, mock_env(), mock_info(&admin(), &[]), msg);
+    res.into_result().unwrap();
+
+    let pairs: Vec<(Scalar, Scalar)> = (1..=inputs as u64).map(|a| (Scalar::from(a), Scalar::from(7))).collect();
+    let proof = verifier_client::prove(params, &pairs, &mut OsRng).unwrap();
+    let valid_msg =
+        verifier_client::verify_proof_msg(&circuit_id, &proof, &Multiplier::products(&pairs), PointEncoding::Uncompressed)
+            .unwrap();
+    let mut rejected_msg = valid_msg.clone();
+    if let QueryMsg::VerifyProof { public_inputs, .. } = &mut rejected_msg {
+        *public_inputs.last_mut().unwrap() = PublicInput::Decimal(NON_CANONICAL_INPUT.to_string());
+    }
+
+    let (verify_gas, verify_time) = average(|_| {
+        gas_used(instance, |instance| assert!(verify(instance, valid_msg.clone()).valid))
+    });
+    let (rejected_gas, rejected_time) = average(|_| {
+        gas_used(instance, |instance| assert!(!verify(instance, rejected_msg.clone()).valid))
+    });
+
+    measurements.push(Measurement { operation: "verify proof", inputs, gas: verify_gas, time: verify_time });
+    measurements.push(Measurement {
+        operation: "rejected before pairing",
+        inputs,
+        gas: rejected_gas,
+        time: rejected_time,
+    });
+    measurements.push(Measurement {
+        operation: "pairing check",
+        inputs,
+        gas: verify_gas - rejected_gas,
+        time: verify_time.saturating_sub(rejected_time),
+    });
+    verify_gas
+}
+
+// The same valid VerifyProof against BASELINE_COMMIT, which decoded, checked and prepared the
+// stored key on every query. Messages are written out in its format
+fn measure_baseline(instance: &mut VmInstance, params: &Parameters<Bls12>, inputs: usize) -> u64 {
+    let circuit_id = format!("verify_{}", inputs);
+    let vkey = verifier_client::vkey_str(&params.vk, PointEncoding::Uncompressed);
+    let msg = json!({"register_verifying_key": {"circuit_id": circuit_id, "vkey": vkey}});
+    let res: ContractResult<Response<Empty>> = execute(instance, mock_env(), mock_info(&admin(), &[]), msg);
+    res.into_result().unwrap();
+
+    let pairs: Vec<(Scalar, Scalar)> = (1..=inputs as u64).map(|a| (Scalar::from(a), Scalar::from(7))).collect();
+    let proof = verifier_client::prove(params, &pairs, &mut OsRng).unwrap();
+    let msg = json!({"verify_proof": {
+        "circuit_id": circuit_id,
+        "proof": serde_json::to_string(&verifier_client::proof_str(&proof, PointEncoding::Uncompressed)).unwrap(),
+        "public_inputs": verifier_client::public_inputs(&Multiplier::products(&pairs)),
+    }});
+    let (gas, _) = average(|_| {
+        gas_used(instance, |instance| {
+            let res: ContractResult<QueryResponse> = query(instance, mock_env(), msg.clone());
+            let response: Value = serde_json::from_slice(res.into_result().unwrap().as_slice()).unwrap();
+            assert_eq!(response["result"], "Done");
+        })
+    });
+    gas
+}
+
+// One VerifyProofBatch of N proofs against N VerifyProof queries for the same proofs, on a one
+// input circuit. The batch shares the final exponentiation, the singles pay one each
+fn measure_batches(instance: &mut VmInstance) -> Vec<BatchMeasurement> {
+    let params = verifier_client::setup(1, &mut OsRng).unwrap();
+    let msg = verifier_client::register_verifying_key_msg("batch", &params.vk, PointEncoding::Uncompressed, None);
+    let res: ContractResult<Response<Empty>> = execute(instance, mock_env(), mock_info(&admin(), &[]), msg);
+    res.into_result().unwrap();
+
+    let mut measurements = Vec::new();
+    for proofs in BATCH_SIZES {
+        let batch: Vec<(String, Vec<PublicInput>)> = (1..=proofs as u64)
+            .map(|a| {
+                let pairs = [(Scalar::from(a), Scalar::from(7))];
+                let proof = verifier_client::prove(&params, &pairs, &mut OsRng).unwrap();
+                let proof_str = verifier_client::proof_str(&proof, PointEncoding::Uncompressed);
+                (serde_json::to_string(&proof_str).unwrap(), verifier_client::public_inputs(&Multiplier::products(&pairs)))
+            })
+            .collect();
+        let single_msgs: Vec<QueryMsg> = batch
+            .iter()
+            .map(|(proof, public_inputs)| QueryMsg::VerifyProof {
+                circuit_id: "batch".to_string(),
+                proof: proof.clone(),
+                public_inputs: public_inputs.clone(),
+                key_version: None,
+                expected_vkey_hash: None,
+                named_inputs: None,
+            })
+            .collect();
+        let batch_msg = QueryMsg::VerifyProofBatch { circuit_id: "batch".to_string(), proofs: batch, key_version: None };
+
+        let (singles_gas, singles_time) = average(|_| {
+            gas_used(instance, |instance| {
+                for msg in single_msgs.iter() {
+                    assert!(verify(instance, msg.clone()).valid);
+                }
+            })
+        });
+        let (batch_gas, batch_time) = average(|_| {
+            gas_used(instance, |instance| {
+                let res: ContractResult<QueryResponse> = query(instance, mock_env(), batch_msg.clone());
+                let response: VerifyProofBatchResponse = serde_json::from_slice(res.into_result().unwrap().as_slice()).unwrap();
+                assert!(response.all_valid);
+            })
+        });
+        measurements.push(BatchMeasurement { proofs, singles_gas, singles_time, batch_gas, batch_time });
+    }
+    measurements
+}
+
+fn verify(instance: &mut VmInstance, msg: QueryMsg) -> VerifyProofResponse {
+    let res: ContractResult<QueryResponse> = query(instance, mock_env(), msg);
+    serde_json::from_slice(res.into_result().unwrap().as_slice()).unwrap()
+}
+
+fn gas_used(instance: &mut VmInstance, call: impl FnOnce(&mut VmInstance)) -> (u64, Duration) {
+    let gas_before = instance.get_gas_left();
+    let start = Instant::now();
+    call(instance);
+    let time = start.elapsed();
+    (gas_before - instance.get_gas_left(), time)
+}
+
+// Gas is deterministic, so only the first run's is kept. Times are averaged over all runs
+fn average(mut run: impl FnMut(u32) -> (u64, Duration)) -> (u64, Duration) {
+    let (gas, mut total) = run(0);
+    for i in 1..RUNS {
+        total += run(i).1;
+    }
+    (gas, total / RUNS)
+}
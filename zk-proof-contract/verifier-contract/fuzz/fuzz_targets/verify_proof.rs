@@ -0,0 +1,55 @@
+#![no_main]
+
+use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{from_json, OwnedDeps};
+use libfuzzer_sys::fuzz_target;
+
+use verifier_contract::contract::{execute, instantiate, query};
+use verifier_contract::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, VerifyProofResponse};
+use verifier_contract::state::{Curve, PublicInput};
+use verifier_contract::{bn254, plonk, state};
+
+thread_local! {
+    // The demo multiplier key registered as "multiply", set up once as inputs only query it
+    static DEPS: OwnedDeps<MockStorage, MockApi, MockQuerier> = {
+        let mut deps = mock_dependencies();
+        let admin = deps.api.addr_make("admin");
+        let msg = InstantiateMsg { admin_address: admin.to_string() };
+        instantiate(deps.as_mut(), mock_env(), message_info(&admin, &[]), msg).unwrap();
+        let register_msg = ExecuteMsg::RegisterVerifyingKey {
+            circuit_id: "multiply".to_string(),
+            vkey: serde_json::from_str(include_str!("../../src/legacy_vkey.json")).unwrap(),
+            nullifier: None,
+            grace_period: None,
+        };
+        execute(deps.as_mut(), mock_env(), message_info(&admin, &[]), register_msg).unwrap();
+        deps
+    };
+}
+
+// Feeds the input as a proof to every parser and to VerifyProof. Nothing may panic, and the query
+// must answer malformed proofs with a failure reason rather than an error.
+fuzz_target!(|data: &[u8]| {
+    let proof = String::from_utf8_lossy(data);
+
+    for curve in [Curve::Bls12_381, Curve::Bn254] {
+        if let Ok(proof_str) = state::decode_proof(&proof, curve) {
+            match curve {
+                Curve::Bls12_381 => drop(state::parse_proof(&proof_str)),
+                Curve::Bn254 => drop(bn254::parse_proof(&proof_str)),
+            }
+        }
+    }
+    let _ = plonk::decode_proof(&proof);
+
+    let query_msg = QueryMsg::VerifyProof {
+        circuit_id: "multiply".to_string(),
+        proof: proof.into_owned(),
+        public_inputs: vec![PublicInput::Decimal("12".to_string())],
+        key_version: None,
+        expected_vkey_hash: None,
+        named_inputs: None,
+    };
+    let response = DEPS.with(|deps| query(deps.as_ref(), mock_env(), query_msg)).unwrap();
+    let _: VerifyProofResponse = from_json(&response).unwrap();
+});
@@ -0,0 +1,155 @@
+use ark_bn254::{Bn254, Fq, Fq12, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::pairing::Pairing;
+use ark_ff::{BigInt, PrimeField};
+use ark_groth16::{Groth16, PreparedVerifyingKey, Proof, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+use crate::state::{
+    check_point, fixed_bytes, public_input_le_bytes, PointEncoding, ProofStr, PublicInput, VkeyStr,
+};
+
+// BN254 points use the layout of the Ethereum pairing precompiles: big-endian coordinates, G2
+// coordinates with c1 before c0, and all zero bytes for the point at infinity. Points are
+// 64 (G1) or 128 (G2) bytes, there is no compressed encoding.
+
+pub fn parse_verification_key(vk: &VkeyStr) -> Result<VerifyingKey<Bn254>, ContractError> {
+    check_encoding(vk.encoding)?;
+
+    // A circuit always has the constant "one" input, so ic can never be empty
+    if vk.ic.is_empty() {
+        return Err(ContractError::InvalidVerifyingKey {});
+    }
+    let mut gamma_abc_g1 = Vec::new();
+    for (i, point) in vk.ic.iter().enumerate() {
+        gamma_abc_g1.push(parse_g1(&format!("ic[{}]", i), point)?);
+    }
+
+    Ok(VerifyingKey {
+        alpha_g1: parse_g1("alpha_1", &vk.alpha_1)?,
+        beta_g2: parse_g2("beta_2", &vk.beta_2)?,
+        gamma_g2: parse_g2("gamma_2", &vk.gamma_2)?,
+        delta_g2: parse_g2("delta_2", &vk.delta_2)?,
+        gamma_abc_g1,
+    })
+}
+
+// Same layout as the BLS12-381 digest in state.rs. BN254 points have a single encoding, so the
+// bytes of a key that parsed are already canonical.
+pub fn verifying_key_digest(vk: &VkeyStr) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"bn254");
+    hasher.update(vk.alpha_1.as_slice());
+    hasher.update(vk.beta_2.as_slice());
+    hasher.update(vk.gamma_2.as_slice());
+    hasher.update(vk.delta_2.as_slice());
+    for point in vk.ic.iter() {
+        hasher.update(point.as_slice());
+    }
+    hasher.finalize().into()
+}
+
+pub fn parse_proof(pof: &ProofStr) -> Result<Proof<Bn254>, ContractError> {
+    check_encoding(pof.encoding)?;
+
+    Ok(Proof {
+        a: parse_g1("pi_a", &pof.pi_a)?,
+        b: parse_g2("pi_b", &pof.pi_b)?,
+        c: parse_g1("pi_c", &pof.pi_c)?,
+    })
+}
+
+pub fn get_public_signal(values: &[PublicInput]) -> Result<Vec<Fr>, ContractError> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            public_input_le_bytes(value)
+                .and_then(fr_from_le_bytes)
+                .ok_or(ContractError::InvalidPublicInput { index })
+        })
+        .collect()
+}
+
+pub(crate) fn fr_from_le_bytes(mut bytes: [u8; 32]) -> Option<Fr> {
+    bytes.reverse();
+    // from_bigint rejects values >= the field modulus
+    Fr::from_bigint(bigint_from_be(&bytes))
+}
+
+// Stores the key with e(alpha, beta) already computed. The prepared G2 points are left out: they
+// are several kilobytes each and cheap to rebuild from the affine points.
+pub fn prepared_key_bytes(vk: &VerifyingKey<Bn254>) -> Result<Vec<u8>, ContractError> {
+    let alpha_g1_beta_g2 = Bn254::pairing(vk.alpha_g1, vk.beta_g2).0;
+    let mut bytes = Vec::new();
+    vk.serialize_uncompressed(&mut bytes).map_err(|_| ContractError::InvalidVerifyingKey {})?;
+    alpha_g1_beta_g2.serialize_uncompressed(&mut bytes).map_err(|_| ContractError::InvalidVerifyingKey {})?;
+    Ok(bytes)
+}
+
+// The points were checked when the key was registered, so they are read back without validation
+pub fn load_prepared_key(mut bytes: &[u8]) -> Result<PreparedVerifyingKey<Bn254>, ContractError> {
+    let vk = VerifyingKey::<Bn254>::deserialize_with_mode(&mut bytes, Compress::No, Validate::No)
+        .map_err(|_| ContractError::InvalidVerifyingKey {})?;
+    let alpha_g1_beta_g2 = Fq12::deserialize_with_mode(&mut bytes, Compress::No, Validate::No)
+        .map_err(|_| ContractError::InvalidVerifyingKey {})?;
+
+    Ok(PreparedVerifyingKey {
+        gamma_g2_neg_pc: (-vk.gamma_g2).into(),
+        delta_g2_neg_pc: (-vk.delta_g2).into(),
+        vk,
+        alpha_g1_beta_g2,
+    })
+}
+
+pub fn verify_proof(pvk: &PreparedVerifyingKey<Bn254>, proof: &Proof<Bn254>, public_inputs: &[Fr]) -> bool {
+    Groth16::<Bn254>::verify_proof(pvk, proof, public_inputs).unwrap_or(false)
+}
+
+fn check_encoding(encoding: PointEncoding) -> Result<(), ContractError> {
+    match encoding {
+        PointEncoding::Uncompressed => Ok(()),
+        PointEncoding::Compressed => Err(ContractError::UnsupportedEncoding {}),
+    }
+}
+
+pub(crate) fn parse_g1(field: &str, bytes: &[u8]) -> Result<G1Affine, ContractError> {
+    let bytes = fixed_bytes::<64>(field, bytes)?;
+    if bytes.iter().all(|byte| *byte == 0) {
+        return Ok(G1Affine::identity());
+    }
+
+    let point = G1Affine::new_unchecked(parse_fq(field, &bytes[..32])?, parse_fq(field, &bytes[32..])?);
+    check_point(field, point.is_on_curve(), point.is_in_correct_subgroup_assuming_on_curve())?;
+    Ok(point)
+}
+
+pub(crate) fn parse_g2(field: &str, bytes: &[u8]) -> Result<G2Affine, ContractError> {
+    let bytes = fixed_bytes::<128>(field, bytes)?;
+    if bytes.iter().all(|byte| *byte == 0) {
+        return Ok(G2Affine::identity());
+    }
+
+    let x = Fq2::new(parse_fq(field, &bytes[32..64])?, parse_fq(field, &bytes[..32])?);
+    let y = Fq2::new(parse_fq(field, &bytes[96..])?, parse_fq(field, &bytes[64..96])?);
+    let point = G2Affine::new_unchecked(x, y);
+    check_point(field, point.is_on_curve(), point.is_in_correct_subgroup_assuming_on_curve())?;
+    Ok(point)
+}
+
+fn parse_fq(field: &str, bytes: &[u8]) -> Result<Fq, ContractError> {
+    // Coordinates at or above the modulus are not canonical
+    Fq::from_bigint(bigint_from_be(bytes)).ok_or_else(|| ContractError::InvalidPointEncoding { field: field.to_string() })
+}
+
+// Expects exactly 32 bytes
+fn bigint_from_be(bytes: &[u8]) -> BigInt<4> {
+    let mut limbs = [0u64; 4];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.rchunks_exact(8)) {
+        let mut limb_bytes = [0u8; 8];
+        limb_bytes.copy_from_slice(chunk);
+        *limb = u64::from_be_bytes(limb_bytes);
+    }
+    BigInt::new(limbs)
+}
@@ -1,15 +1,35 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, to_binary};
-use cw2::set_contract_version;
-use bellman::groth16::verify_proof;
+use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, Storage, to_json_binary};
+use cw2::{get_contract_version, set_contract_version};
+use contract_migration::{Migration, Version};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, VerifyTruthResponse, VerifyProofResponse};
-use crate::state::{Config, CONFIG, get_verification_key, parse_proof, get_public_signal};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, VerifyTruthResponse};
+use crate::state::{Config, CIRCUITS, CONFIG, VERIFYING_KEYS, GracePeriod, KeyRegistration, ProofSystem, VkeyStr};
 
-const CONTRACT_NAME: &str = "crates.io:verifier-contract";
+mod admin;
+mod groups;
+mod keys;
+mod nullifiers;
+mod verify;
+
+const CONTRACT_NAME: &str = "crates.io:zk-verifier-contract";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION"); 
+// 0.1.x stored nothing but its config, as the same_chain verifier did under the same name. Only
+// the legacy_contract confirmation in the migrate message tells the two apart
+const MIGRATION: Migration = Migration {
+    contract: CONTRACT_NAME,
+    version: CONTRACT_VERSION,
+    legacy_contract: "crates.io:verifier-contract",
+};
+// The multiplier key 0.1.x verified every proof against
+const LEGACY_VKEY: &str = include_str!("legacy_vkey.json");
+
+fn legacy_layout(storage: &dyn Storage) -> bool {
+    matches!(CONFIG.may_load(storage), Ok(Some(_)))
+        && CIRCUITS.keys(storage, None, None, Order::Ascending).next().is_none()
+}
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -23,7 +43,8 @@ pub fn instantiate(
     let validated_admin_address = deps.api.addr_validate(&msg.admin_address)?;
 
     let config = Config {
-        admin_address: validated_admin_address
+        admin_address: Some(validated_admin_address),
+        pending_admin: None,
     };
 
     CONFIG.save(deps.storage, &config)?;
@@ -33,34 +54,127 @@ pub fn instantiate(
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
-    _deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
-    _msg: ExecuteMsg,
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
-    unimplemented!()
+    match msg {
+        ExecuteMsg::RegisterVerifyingKey { circuit_id, vkey, nullifier, grace_period } => {
+            keys::register_verifying_key(deps, env, info, circuit_id, vkey, nullifier, grace_period)
+        }
+        ExecuteMsg::RegisterSnarkjsVerifyingKey { circuit_id, vkey, nullifier, grace_period } => {
+            keys::register_verifying_key(deps, env, info, circuit_id, vkey.to_vkey_str()?, nullifier, grace_period)
+        }
+        ExecuteMsg::RegisterTemplateKey { circuit_id, template, vkey, grace_period } => {
+            keys::register_template_key(deps, env, info, circuit_id, template, vkey, grace_period)
+        }
+        ExecuteMsg::RegisterPlonkVerifyingKey { circuit_id, vkey, nullifier, grace_period } => {
+            keys::register_plonk_verifying_key(deps, env, info, circuit_id, vkey, nullifier, grace_period)
+        }
+        ExecuteMsg::ActivateVerifyingKey { circuit_id, version } => {
+            keys::activate_verifying_key(deps, env, info, circuit_id, version)
+        }
+        ExecuteMsg::DeprecateVerifyingKey { circuit_id, version, grace_period } => {
+            keys::deprecate_verifying_key(deps, env, info, circuit_id, version, grace_period)
+        }
+        ExecuteMsg::RevokeVerifyingKey { circuit_id, version } => keys::revoke_verifying_key(deps, info, circuit_id, version),
+        ExecuteMsg::SetInputNames { circuit_id, input_names } => keys::set_input_names(deps, info, circuit_id, input_names),
+        ExecuteMsg::SetMsgsHashInput { circuit_id, index } => keys::set_msgs_hash_input(deps, info, circuit_id, index),
+        ExecuteMsg::SubmitProof { circuit_id, proof, public_inputs, key_version } => {
+            nullifiers::submit_proof(deps, env, circuit_id, proof, public_inputs, key_version)
+        }
+        ExecuteMsg::VerifyAndExecute { circuit_id, proof, public_inputs, msgs, key_version } => {
+            nullifiers::verify_and_execute(deps, env, circuit_id, proof, public_inputs, msgs, key_version)
+        }
+        ExecuteMsg::CreateMerkleTree { tree_id, circuit_id, root_index, owner, history_size } => {
+            groups::create_merkle_tree(deps, info, tree_id, circuit_id, root_index, owner, history_size)
+        }
+        ExecuteMsg::UpdateMerkleRoot { tree_id, root } => groups::update_merkle_root(deps, info, tree_id, root),
+        ExecuteMsg::CreateGroup { group_id, circuit_id, depth, admin, history_size } => {
+            groups::create_group(deps, info, group_id, circuit_id, depth, admin, history_size)
+        }
+        ExecuteMsg::AddMember { group_id, identity_commitment } => groups::add_member(deps, info, group_id, identity_commitment),
+        ExecuteMsg::Signal { group_id, merkle_tree_root, signal, nullifier_hash, external_nullifier, proof } => {
+            groups::send_signal(deps, env, group_id, merkle_tree_root, signal, nullifier_hash, external_nullifier, proof)
+        }
+        ExecuteMsg::UpdateAdmin { admin_address } => admin::update_admin(deps, info, admin_address),
+        ExecuteMsg::ProposeAdmin { admin_address } => admin::propose_admin(deps, info, admin_address),
+        ExecuteMsg::AcceptAdmin {} => admin::accept_admin(deps, info),
+        ExecuteMsg::RenounceAdmin {} => admin::renounce_admin(deps, info),
+    }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(_deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
     match msg {
         // This method checks if sum of a and b equates to sum
-        QueryMsg::VerifyTruth { a, b, sum } => query_verify_truth(a, b, sum),
-        QueryMsg::VerifyProof { proof, expected_value } => verify_the_zk_proof(proof, expected_value)
+        QueryMsg::VerifyTruth { a, b, sum } => Ok(query_verify_truth(a, b, sum)?),
+        QueryMsg::VerifyProof { circuit_id, proof, public_inputs, key_version, expected_vkey_hash, named_inputs } => {
+            let public_inputs = match named_inputs {
+                Some(named_inputs) => verify::positional_inputs(deps, &circuit_id, public_inputs, named_inputs)?,
+                None => public_inputs,
+            };
+            verify::verify_the_zk_proof(deps, env, circuit_id, proof, public_inputs, key_version, expected_vkey_hash)
+        }
+        QueryMsg::VerifyProofBatch { circuit_id, proofs, key_version } => {
+            verify::verify_the_zk_proof_batch(deps, env, circuit_id, proofs, key_version)
+        }
+        QueryMsg::ListCircuits { start_after, limit } => Ok(to_json_binary(&keys::list_circuits(deps, start_after, limit)?)?),
+        QueryMsg::Circuit { id } => Ok(to_json_binary(&keys::query_circuit(deps, id)?)?),
+        QueryMsg::ListKeyVersions { circuit_id, start_after, limit } => {
+            Ok(to_json_binary(&keys::list_key_versions(deps, env, circuit_id, start_after, limit)?)?)
+        }
+        QueryMsg::VerifyMembershipProof { tree_id, proof, public_inputs, key_version, expected_vkey_hash } => {
+            groups::verify_membership_proof(deps, env, tree_id, proof, public_inputs, key_version, expected_vkey_hash)
+        }
+        QueryMsg::MerkleTree { tree_id } => Ok(to_json_binary(&groups::query_merkle_tree(deps, tree_id)?)?),
+        QueryMsg::MsgsHash { msgs } => Ok(to_json_binary(&nullifiers::query_msgs_hash(&msgs)?)?),
+        QueryMsg::CircuitTemplates {} => Ok(to_json_binary(&keys::query_circuit_templates())?),
+        QueryMsg::Group { group_id } => Ok(to_json_binary(&groups::query_group(deps, group_id)?)?),
+        QueryMsg::Config {} => Ok(to_json_binary(&admin::query_config(deps)?)?),
     }
 }
 
-fn verify_the_zk_proof(proof: String, expected_value: u64) -> StdResult<Binary> {
-    let vkey = get_verification_key();
-    let parsed_proof = parse_proof(proof);
-    let public_signal = get_public_signal(expected_value);
-    let result = verify_proof(&vkey, &parsed_proof, &public_signal).is_ok();
-    
-    if result {
-        to_binary(&VerifyProofResponse{ result: "Done".to_string() })
-    } else {
-        to_binary(&VerifyProofResponse{ result: "Not Done".to_string() })
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let previous = get_contract_version(deps.storage)?;
+    let previous_version =
+        MIGRATION.check(&previous.contract, &previous.version, msg.legacy_contract.as_deref(), || legacy_layout(deps.storage))?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    if previous_version < Version::new(0, 2, 0) {
+        migrate_to_key_registry(deps.branch(), &env, msg.legacy_circuit_id)?;
     }
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("previous_version", previous_version.to_string())
+        .add_attribute("version", CONTRACT_VERSION))
+}
+
+// 0.1.x stored only the config and verified against a key compiled into the contract. The config
+// still loads as is, it is saved again so it is stored in the current layout.
+fn migrate_to_key_registry(mut deps: DepsMut, env: &Env, legacy_circuit_id: Option<String>) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    if let Some(circuit_id) = legacy_circuit_id {
+        let vkey: VkeyStr = serde_json::from_str(LEGACY_VKEY).map_err(|_| ContractError::InvalidVerifyingKey {})?;
+        let (prepared_key, key_hash) = keys::prepare_key(&vkey)?;
+        // Nobody sends a migration, so the contract itself is recorded as the registrant
+        let registration = KeyRegistration {
+            key_hash: hex::encode(key_hash),
+            curve: vkey.curve,
+            num_public_inputs: (vkey.ic.len() - 1) as u32,
+            registered_height: env.block.height,
+            registrant: env.contract.address.clone(),
+        };
+        let until = GracePeriod::ROTATION_DEFAULT.deadline(&env.block)?;
+        let version =
+            keys::add_key_version(deps.branch(), &circuit_id, ProofSystem::Groth16, prepared_key, &registration, None, until)?;
+        VERIFYING_KEYS.save(deps.storage, (&circuit_id, version), &vkey)?;
+    }
+    Ok(())
 }
 
 fn query_verify_truth(a: u64, b: u64, sum: u64) -> StdResult<Binary> {
@@ -70,56 +184,8 @@ fn query_verify_truth(a: u64, b: u64, sum: u64) -> StdResult<Binary> {
         is_valid_sum = true;
     }
 
-    to_binary(&VerifyTruthResponse { valid: is_valid_sum })
+    to_json_binary(&VerifyTruthResponse { valid: is_valid_sum })
 }
 
 #[cfg(test)]
-mod tests {
-    use cosmwasm_std::{testing::{mock_dependencies, mock_env, mock_info}, from_binary};
-    use crate::{msg::{InstantiateMsg, QueryMsg, VerifyTruthResponse}};
-    use super::{instantiate, query};
-
-    #[test]
-    fn test_verify_truth_invalid_case() {
-        let mut deps = mock_dependencies();
-        let env = mock_env();
-        let info = mock_info("addr1", &[]);
-        let msg = InstantiateMsg{
-            admin_address: "addr1".to_string()
-        };
-        let _ = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
-        
-        // Invalid Inputs
-        let invalid_query_msg = QueryMsg::VerifyTruth { a: 1, b: 2, sum: 3 };
-        let query_response = query(deps.as_ref(), env.clone(), invalid_query_msg).unwrap();
-        let get_validity: VerifyTruthResponse = from_binary(&query_response).unwrap();
-        assert_eq!(
-            get_validity,
-            VerifyTruthResponse {
-                valid: false
-            }
-        );
-    }
-
-    #[test]
-    fn test_verify_truth_valid_case() {
-        let mut deps = mock_dependencies();
-        let env = mock_env();
-        let info = mock_info("addr1", &[]);
-        let msg = InstantiateMsg{
-            admin_address: "addr1".to_string()
-        };
-        let _ = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
-
-        // Valid inputs
-        let valid_query_msg = QueryMsg::VerifyTruth { a: 10, b: 2, sum: 12 };
-        let query_response = query(deps.as_ref(), env.clone(), valid_query_msg).unwrap();
-        let get_validity: VerifyTruthResponse = from_binary(&query_response).unwrap();
-        assert_eq!(
-            get_validity,
-            VerifyTruthResponse {
-                valid: true
-            }
-        );
-    }
-}
+mod tests;
@@ -0,0 +1,72 @@
+use cosmwasm_std::{Deps, DepsMut, MessageInfo, Response, StdResult};
+
+use crate::error::ContractError;
+use crate::msg::ConfigResponse;
+use crate::state::{Config, CONFIG};
+
+// The admin, who alone registers and rotates keys, and the two step handover of that role
+
+pub(super) fn check_admin(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.admin_address.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+pub(super) fn update_admin(deps: DepsMut, info: MessageInfo, admin_address: String) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    let admin_address = deps.api.addr_validate(&admin_address)?;
+    let config = Config { admin_address: Some(admin_address.clone()), pending_admin: None };
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_admin")
+        .add_attribute("admin_address", admin_address))
+}
+
+pub(super) fn propose_admin(deps: DepsMut, info: MessageInfo, admin_address: String) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    let pending_admin = deps.api.addr_validate(&admin_address)?;
+    let mut config = CONFIG.load(deps.storage)?;
+    config.pending_admin = Some(pending_admin.clone());
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_admin")
+        .add_attribute("pending_admin", pending_admin))
+}
+
+pub(super) fn accept_admin(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    match config.pending_admin {
+        None => return Err(ContractError::NoPendingAdmin {}),
+        Some(pending_admin) if pending_admin != info.sender => return Err(ContractError::Unauthorized {}),
+        Some(_) => {}
+    }
+    let config = Config { admin_address: Some(info.sender.clone()), pending_admin: None };
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_admin")
+        .add_attribute("admin_address", info.sender))
+}
+
+pub(super) fn renounce_admin(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    let config = Config { admin_address: None, pending_admin: None };
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attribute("action", "renounce_admin"))
+}
+
+pub(super) fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        admin_address: config.admin_address,
+        pending_admin: config.pending_admin,
+    })
+}
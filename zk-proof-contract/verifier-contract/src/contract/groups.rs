@@ -0,0 +1,299 @@
+use bls12_381::Scalar;
+use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, Uint256, to_json_binary};
+use ff::PrimeField;
+
+use crate::bn254;
+use crate::error::ContractError;
+use crate::msg::{GroupResponse, MerkleTreeResponse, VerifyProofResponse};
+use crate::semaphore;
+use crate::state::{
+    CIRCUITS, GROUPS, KEY_REGISTRATIONS, MAX_ROOT_HISTORY, MERKLE_ROOTS, MERKLE_TREES, SIGNAL_NULLIFIERS, Curve, Group,
+    MerkleTree,
+    ProofSystem, PublicInput, public_input_le_bytes,
+};
+
+use super::admin::check_admin;
+use super::keys::{candidate_versions, pin_key_hash};
+use super::verify::{failure_reason, verify_against_versions};
+
+// Merkle trees whose roots a membership proof is checked against, and Semaphore groups, whose
+// roots the contract computes itself as members are added
+
+pub(super) fn create_merkle_tree(
+    deps: DepsMut,
+    info: MessageInfo,
+    tree_id: String,
+    circuit_id: String,
+    root_index: usize,
+    owner: Option<String>,
+    history_size: u32,
+) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    if MERKLE_TREES.has(deps.storage, &tree_id) {
+        return Err(ContractError::MerkleTreeExists { tree_id });
+    }
+    let circuit = CIRCUITS.load(deps.storage, &circuit_id)?;
+    let version = circuit.active_version.unwrap_or(circuit.latest_version);
+    let registration = KEY_REGISTRATIONS.load(deps.storage, (&circuit_id, version))?;
+    if root_index >= registration.num_public_inputs as usize {
+        return Err(ContractError::InvalidRootIndex { index: root_index });
+    }
+    if history_size == 0 || history_size > MAX_ROOT_HISTORY {
+        return Err(ContractError::InvalidHistorySize { size: history_size, max: MAX_ROOT_HISTORY });
+    }
+    let owner = owner.map(|owner| deps.api.addr_validate(&owner)).transpose()?;
+
+    let tree = MerkleTree { circuit_id, root_index, curve: registration.curve, owner, history_size, roots: vec![] };
+    MERKLE_TREES.save(deps.storage, &tree_id, &tree)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_merkle_tree")
+        .add_attribute("tree_id", tree_id)
+        .add_attribute("circuit_id", tree.circuit_id))
+}
+
+pub(super) fn update_merkle_root(
+    deps: DepsMut,
+    info: MessageInfo,
+    tree_id: String,
+    root: PublicInput,
+) -> Result<Response, ContractError> {
+    let mut tree = MERKLE_TREES.load(deps.storage, &tree_id)?;
+    if tree.owner.as_ref() != Some(&info.sender) {
+        check_admin(deps.as_ref(), &info)?;
+    }
+
+    let root = parse_root(tree.curve, &root)?;
+    // A root published again moves to the front of the history
+    tree.roots.retain(|accepted| accepted.as_slice() != root);
+    for dropped in push_root(&mut tree.roots, Binary::from(root.as_ref()), tree.history_size) {
+        MERKLE_ROOTS.remove(deps.storage, (&tree_id, dropped.as_slice()));
+    }
+    MERKLE_ROOTS.save(deps.storage, (&tree_id, &root), &())?;
+    MERKLE_TREES.save(deps.storage, &tree_id, &tree)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_merkle_root")
+        .add_attribute("tree_id", tree_id)
+        .add_attribute("root", hex::encode(root)))
+}
+
+// Roots are public inputs of the tree's circuit, so they must be below its scalar field modulus.
+// Any other root could never be proven against
+fn parse_root(curve: Curve, root: &PublicInput) -> Result<[u8; 32], ContractError> {
+    let root = public_input_le_bytes(root).ok_or(ContractError::InvalidMerkleRoot {})?;
+    let canonical = match curve {
+        Curve::Bls12_381 => bool::from(Scalar::from_repr(root).is_some()),
+        Curve::Bn254 => bn254::fr_from_le_bytes(root).is_some(),
+    };
+    if !canonical {
+        return Err(ContractError::InvalidMerkleRoot {});
+    }
+    Ok(root)
+}
+
+// Makes root the latest one, dropping the oldest roots beyond history_size. Returns the dropped
+// roots
+fn push_root(roots: &mut Vec<Binary>, root: Binary, history_size: u32) -> Vec<Binary> {
+    roots.push(root);
+    let excess = roots.len().saturating_sub(history_size as usize);
+    roots.drain(..excess).collect()
+}
+
+pub(super) fn create_group(
+    deps: DepsMut,
+    info: MessageInfo,
+    group_id: Uint256,
+    circuit_id: String,
+    depth: u8,
+    admin: Option<String>,
+    history_size: u32,
+) -> Result<Response, ContractError> {
+    let group_key = group_id.to_string();
+    if GROUPS.has(deps.storage, &group_key) {
+        return Err(ContractError::GroupExists { group_id: group_key });
+    }
+    if depth == 0 || depth > semaphore::MAX_DEPTH {
+        return Err(ContractError::InvalidGroupDepth { depth, max: semaphore::MAX_DEPTH });
+    }
+    if history_size == 0 || history_size > MAX_ROOT_HISTORY {
+        return Err(ContractError::InvalidHistorySize { size: history_size, max: MAX_ROOT_HISTORY });
+    }
+    let circuit = CIRCUITS.load(deps.storage, &circuit_id)?;
+    let version = circuit.active_version.unwrap_or(circuit.latest_version);
+    let registration = KEY_REGISTRATIONS.load(deps.storage, (&circuit_id, version))?;
+    if circuit.proof_system != ProofSystem::Groth16
+        || registration.curve != Curve::Bn254
+        || registration.num_public_inputs != 4
+    {
+        return Err(ContractError::InvalidGroupCircuit { circuit_id });
+    }
+    let admin = match admin {
+        Some(admin) => deps.api.addr_validate(&admin)?,
+        None => info.sender,
+    };
+
+    let (zeroes, root) = semaphore::new_tree(group_id, depth);
+    let group = Group {
+        circuit_id,
+        admin,
+        depth,
+        history_size,
+        num_members: 0,
+        filled_subtrees: zeroes.clone(),
+        zeroes,
+        roots: vec![root.clone()],
+    };
+    GROUPS.save(deps.storage, &group_key, &group)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_group")
+        .add_attribute("group_id", group_key)
+        .add_attribute("circuit_id", group.circuit_id)
+        .add_attribute("admin", group.admin)
+        .add_attribute("root", hex::encode(root.as_slice())))
+}
+
+pub(super) fn add_member(
+    deps: DepsMut,
+    info: MessageInfo,
+    group_id: Uint256,
+    identity_commitment: PublicInput,
+) -> Result<Response, ContractError> {
+    let group_key = group_id.to_string();
+    let mut group = GROUPS.load(deps.storage, &group_key)?;
+    if info.sender != group.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    if group.num_members >= 1u64 << group.depth {
+        return Err(ContractError::GroupFull { group_id: group_key });
+    }
+
+    let leaf = public_input_le_bytes(&identity_commitment)
+        .and_then(bn254::fr_from_le_bytes)
+        .ok_or(ContractError::InvalidIdentityCommitment {})?;
+    let index = group.num_members;
+    let root = semaphore::insert(&mut group, leaf)?;
+    push_root(&mut group.roots, root.clone(), group.history_size);
+    GROUPS.save(deps.storage, &group_key, &group)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_member")
+        .add_attribute("group_id", group_key)
+        .add_attribute("index", index.to_string())
+        .add_attribute("identity_commitment", hex::encode(semaphore::fr_to_binary(leaf).as_slice()))
+        .add_attribute("root", hex::encode(root.as_slice())))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn send_signal(
+    deps: DepsMut,
+    env: Env,
+    group_id: Uint256,
+    merkle_tree_root: PublicInput,
+    signal: Uint256,
+    nullifier_hash: PublicInput,
+    external_nullifier: Uint256,
+    proof: String,
+) -> Result<Response, ContractError> {
+    let group_key = group_id.to_string();
+    let group = GROUPS.load(deps.storage, &group_key)?;
+
+    let root = public_input_le_bytes(&merkle_tree_root).ok_or(ContractError::InvalidMerkleRoot {})?;
+    if !group.roots.iter().any(|accepted| accepted.as_slice() == root) {
+        return Err(ContractError::MerkleRootNotAccepted { root: hex::encode(root) });
+    }
+    let nullifier = public_input_le_bytes(&nullifier_hash).ok_or(ContractError::InvalidPublicInput { index: 1 })?;
+    if SIGNAL_NULLIFIERS.has(deps.storage, (&group_key, &nullifier)) {
+        return Err(ContractError::NullifierAlreadyUsed { nullifier: hex::encode(nullifier) });
+    }
+
+    // The order of the Semaphore circuit's public signals
+    let public_inputs = vec![
+        PublicInput::HexLe(hex::encode(root)),
+        PublicInput::HexLe(hex::encode(nullifier)),
+        root_input(&semaphore::fr_to_binary(semaphore::hash_to_field(signal))),
+        root_input(&semaphore::fr_to_binary(semaphore::hash_to_field(external_nullifier))),
+    ];
+    let versions = candidate_versions(deps.as_ref(), &env, &group.circuit_id, None)?;
+    let (version, _) = verify_against_versions(deps.as_ref(), &group.circuit_id, &versions, &proof, &public_inputs)?;
+    SIGNAL_NULLIFIERS.save(deps.storage, (&group_key, &nullifier), &env.block.height)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "signal")
+        .add_attribute("group_id", group_key)
+        .add_attribute("signal", signal.to_string())
+        .add_attribute("external_nullifier", external_nullifier.to_string())
+        .add_attribute("nullifier_hash", hex::encode(nullifier))
+        .add_attribute("key_version", version.to_string()))
+}
+
+pub(super) fn verify_membership_proof(
+    deps: Deps,
+    env: Env,
+    tree_id: String,
+    proof: String,
+    public_inputs: Vec<PublicInput>,
+    key_version: Option<u64>,
+    expected_vkey_hash: Option<String>,
+) -> Result<Binary, ContractError> {
+    let tree = MERKLE_TREES.load(deps.storage, &tree_id)?;
+    if tree.roots.is_empty() {
+        return Err(ContractError::NoMerkleRoot { tree_id });
+    }
+    // With too few inputs there is no root to look up, and the count check rejects the proof
+    if let Some(root) = public_inputs.get(tree.root_index) {
+        let root = public_input_le_bytes(root).ok_or(ContractError::InvalidMerkleRoot {})?;
+        if !MERKLE_ROOTS.has(deps.storage, (&tree_id, &root)) {
+            return Err(ContractError::MerkleRootNotAccepted { root: hex::encode(root) });
+        }
+    }
+
+    let circuit_id = tree.circuit_id;
+    let mut versions = candidate_versions(deps, &env, &circuit_id, key_version)?;
+    if let Some(expected_vkey_hash) = expected_vkey_hash {
+        versions = pin_key_hash(deps, &circuit_id, versions, &expected_vkey_hash)?;
+    }
+    let (key_version, failure) = match verify_against_versions(deps, &circuit_id, &versions, &proof, &public_inputs) {
+        Ok((version, _)) => (version, None),
+        Err(err) => (versions[0], Some(failure_reason(err)?)),
+    };
+
+    Ok(to_json_binary(&VerifyProofResponse {
+        valid: failure.is_none(),
+        circuit_id,
+        key_version,
+        public_inputs,
+        failure,
+    })?)
+}
+
+pub(super) fn root_input(root: &Binary) -> PublicInput {
+    PublicInput::HexLe(hex::encode(root.as_slice()))
+}
+
+pub(super) fn query_merkle_tree(deps: Deps, tree_id: String) -> Result<MerkleTreeResponse, ContractError> {
+    let tree = MERKLE_TREES.load(deps.storage, &tree_id)?;
+    Ok(MerkleTreeResponse {
+        tree_id,
+        roots: tree.roots.iter().rev().map(root_input).collect(),
+        circuit_id: tree.circuit_id,
+        root_index: tree.root_index,
+        owner: tree.owner,
+        history_size: tree.history_size,
+    })
+}
+
+pub(super) fn query_group(deps: Deps, group_id: Uint256) -> Result<GroupResponse, ContractError> {
+    let group = GROUPS.load(deps.storage, &group_id.to_string())?;
+    Ok(GroupResponse {
+        group_id,
+        roots: group.roots.iter().rev().map(root_input).collect(),
+        circuit_id: group.circuit_id,
+        admin: group.admin,
+        depth: group.depth,
+        num_members: group.num_members,
+        history_size: group.history_size,
+    })
+}
@@ -0,0 +1,484 @@
+use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult};
+use cw_storage_plus::Bound;
+
+use crate::bn254;
+use crate::error::ContractError;
+use crate::msg::{
+    CircuitResponse, CircuitTemplateInfo, CircuitTemplatesResponse, CircuitsResponse, KeyVersionInfo, KeyVersionsResponse,
+};
+use crate::plonk::{self, PlonkVkey};
+use crate::snarkjs::SnarkjsVkey;
+use crate::state::{
+    CIRCUITS, KEY_REGISTRATIONS, KEY_STATUSES, PLONK_KEYS, PREPARED_KEYS, VERIFYING_KEYS, CircuitInfo, CircuitTemplate, Curve,
+    Deadline, GracePeriod, InputNames, KeyRegistration, KeyStatus, NullifierSource, PreparedVerifyingKey, ProofSystem, VkeyStr,
+    parse_verification_key, verifying_key_digest,
+};
+
+use super::admin::check_admin;
+
+// The key registry: circuits, their key versions and how a version moves from active to deprecated
+// or revoked, and which versions a proof is checked against
+
+pub(super) fn register_verifying_key(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    circuit_id: String,
+    vkey: VkeyStr,
+    nullifier: Option<NullifierSource>,
+    grace_period: Option<GracePeriod>,
+) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    // Also rejects keys with malformed points before they can be used for verification
+    let (prepared_key, key_hash) = prepare_key(&vkey)?;
+    let registration = KeyRegistration {
+        key_hash: hex::encode(key_hash),
+        curve: vkey.curve,
+        num_public_inputs: (vkey.ic.len() - 1) as u32,
+        registered_height: env.block.height,
+        registrant: info.sender,
+    };
+    let until = grace_period.unwrap_or(GracePeriod::ROTATION_DEFAULT).deadline(&env.block)?;
+    let version =
+        add_key_version(deps.branch(), &circuit_id, ProofSystem::Groth16, prepared_key, &registration, nullifier, until)?;
+    VERIFYING_KEYS.save(deps.storage, (&circuit_id, version), &vkey)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_verifying_key")
+        .add_attribute("circuit_id", circuit_id)
+        .add_attribute("key_version", version.to_string())
+        .add_attribute("key_hash", registration.key_hash))
+}
+
+pub(super) fn register_template_key(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    circuit_id: String,
+    template: CircuitTemplate,
+    vkey: SnarkjsVkey,
+    grace_period: Option<GracePeriod>,
+) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    let vkey = vkey.to_vkey_str()?;
+    if vkey.curve != template.curve() {
+        return Err(ContractError::TemplateCurveMismatch { template: template.name().to_string() });
+    }
+    let input_names = InputNames::Template(template);
+    check_input_name_count(&input_names, vkey.ic.len().saturating_sub(1) as u32)?;
+    let response = register_verifying_key(deps.branch(), env, info, circuit_id.clone(), vkey, None, grace_period)?;
+    let mut circuit = CIRCUITS.load(deps.storage, &circuit_id)?;
+    circuit.input_names = Some(input_names);
+    CIRCUITS.save(deps.storage, &circuit_id, &circuit)?;
+
+    Ok(response.add_attribute("template", template.name()))
+}
+
+pub(super) fn register_plonk_verifying_key(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    circuit_id: String,
+    vkey: PlonkVkey,
+    nullifier: Option<NullifierSource>,
+    grace_period: Option<GracePeriod>,
+) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    let parsed_vkey = plonk::parse_verification_key(&vkey)?;
+    let registration = KeyRegistration {
+        key_hash: hex::encode(parsed_vkey.digest()),
+        curve: Curve::Bn254,
+        num_public_inputs: vkey.n_public as u32,
+        registered_height: env.block.height,
+        registrant: info.sender,
+    };
+    let prepared_key = parsed_vkey.to_bytes()?;
+    let until = grace_period.unwrap_or(GracePeriod::ROTATION_DEFAULT).deadline(&env.block)?;
+    let version =
+        add_key_version(deps.branch(), &circuit_id, ProofSystem::Plonk, prepared_key, &registration, nullifier, until)?;
+    PLONK_KEYS.save(deps.storage, (&circuit_id, version), &vkey)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "register_plonk_verifying_key")
+        .add_attribute("circuit_id", circuit_id)
+        .add_attribute("key_version", version.to_string())
+        .add_attribute("key_hash", registration.key_hash))
+}
+
+// Records the next key version of the circuit, creating the circuit if needed, and makes it the
+// active one. The previous active key stays accepted until the given deadline. The caller stores
+// the key itself under the returned version.
+pub(super) fn add_key_version(
+    deps: DepsMut,
+    circuit_id: &str,
+    proof_system: ProofSystem,
+    prepared_key: Vec<u8>,
+    registration: &KeyRegistration,
+    nullifier: Option<NullifierSource>,
+    previous_until: Deadline,
+) -> Result<u64, ContractError> {
+    let mut circuit = match CIRCUITS.may_load(deps.storage, circuit_id)? {
+        Some(circuit) if circuit.proof_system != proof_system => {
+            return Err(ContractError::ProofSystemMismatch { circuit_id: circuit_id.to_string() });
+        }
+        Some(circuit) => circuit,
+        None => CircuitInfo { proof_system, ..CircuitInfo::default() },
+    };
+    if nullifier.is_some() {
+        circuit.nullifier = nullifier;
+    }
+    if let Some(NullifierSource::PublicInput { index }) = circuit.nullifier {
+        if index >= registration.num_public_inputs as usize {
+            return Err(ContractError::InvalidNullifierIndex { index });
+        }
+        // Proofs for older keys are still accepted, and deprecated keys can be activated again, so
+        // the index has to fit every key that is not revoked
+        for item in KEY_STATUSES.prefix(circuit_id).range(deps.storage, None, None, Order::Ascending) {
+            let (version, status) = item?;
+            if status != KeyStatus::Revoked
+                && index >= KEY_REGISTRATIONS.load(deps.storage, (circuit_id, version))?.num_public_inputs as usize
+            {
+                return Err(ContractError::InvalidNullifierIndex { index });
+            }
+        }
+    }
+    if let Some(input_names) = circuit.input_names.as_ref() {
+        check_input_name_count(input_names, registration.num_public_inputs)?;
+    }
+    if let Some(index) = circuit.msgs_hash_index.filter(|index| *index >= registration.num_public_inputs as usize) {
+        return Err(ContractError::InvalidMsgsHashIndex { index });
+    }
+
+    let version = circuit.latest_version + 1;
+    if let Some(active_version) = circuit.active_version {
+        let status = KeyStatus::Deprecated { until: Some(previous_until) };
+        KEY_STATUSES.save(deps.storage, (circuit_id, active_version), &status)?;
+    }
+    PREPARED_KEYS.save(deps.storage, (circuit_id, version), &Binary::new(prepared_key))?;
+    KEY_STATUSES.save(deps.storage, (circuit_id, version), &KeyStatus::Active)?;
+    KEY_REGISTRATIONS.save(deps.storage, (circuit_id, version), registration)?;
+    circuit.latest_version = version;
+    circuit.active_version = Some(version);
+    CIRCUITS.save(deps.storage, circuit_id, &circuit)?;
+    Ok(version)
+}
+
+pub(super) fn activate_verifying_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    circuit_id: String,
+    version: u64,
+) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    let mut circuit = CIRCUITS.load(deps.storage, &circuit_id)?;
+    if load_key_status(deps.as_ref(), &circuit_id, version)? == KeyStatus::Revoked {
+        return Err(ContractError::KeyRevoked { version });
+    }
+    if let Some(active_version) = circuit.active_version.filter(|active_version| *active_version != version) {
+        let until = GracePeriod::ROTATION_DEFAULT.deadline(&env.block)?;
+        KEY_STATUSES.save(deps.storage, (&circuit_id, active_version), &KeyStatus::Deprecated { until: Some(until) })?;
+    }
+    KEY_STATUSES.save(deps.storage, (&circuit_id, version), &KeyStatus::Active)?;
+    circuit.active_version = Some(version);
+    CIRCUITS.save(deps.storage, &circuit_id, &circuit)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "activate_verifying_key")
+        .add_attribute("circuit_id", circuit_id)
+        .add_attribute("key_version", version.to_string()))
+}
+
+pub(super) fn deprecate_verifying_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    circuit_id: String,
+    version: u64,
+    grace_period: Option<GracePeriod>,
+) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    if load_key_status(deps.as_ref(), &circuit_id, version)? == KeyStatus::Revoked {
+        return Err(ContractError::KeyRevoked { version });
+    }
+    let until = grace_period.map(|grace_period| grace_period.deadline(&env.block)).transpose()?;
+    KEY_STATUSES.save(deps.storage, (&circuit_id, version), &KeyStatus::Deprecated { until })?;
+    clear_active_version(deps, &circuit_id, version)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "deprecate_verifying_key")
+        .add_attribute("circuit_id", circuit_id)
+        .add_attribute("key_version", version.to_string()))
+}
+
+pub(super) fn revoke_verifying_key(
+    deps: DepsMut,
+    info: MessageInfo,
+    circuit_id: String,
+    version: u64,
+) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    load_key_status(deps.as_ref(), &circuit_id, version)?;
+    KEY_STATUSES.save(deps.storage, (&circuit_id, version), &KeyStatus::Revoked)?;
+    clear_active_version(deps, &circuit_id, version)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke_verifying_key")
+        .add_attribute("circuit_id", circuit_id)
+        .add_attribute("key_version", version.to_string()))
+}
+
+pub(super) fn set_input_names(
+    deps: DepsMut,
+    info: MessageInfo,
+    circuit_id: String,
+    input_names: InputNames,
+) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    let mut circuit = CIRCUITS.load(deps.storage, &circuit_id)?;
+    let version = circuit.active_version.unwrap_or(circuit.latest_version);
+    let registration = KEY_REGISTRATIONS.load(deps.storage, (&circuit_id, version))?;
+    check_input_name_count(&input_names, registration.num_public_inputs)?;
+    let names = input_names.names();
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            return Err(ContractError::DuplicateInputName { name: name.clone() });
+        }
+    }
+    circuit.input_names = Some(input_names);
+    CIRCUITS.save(deps.storage, &circuit_id, &circuit)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_input_names")
+        .add_attribute("circuit_id", circuit_id)
+        .add_attribute("input_names", names.join(",")))
+}
+
+pub(super) fn set_msgs_hash_input(
+    deps: DepsMut,
+    info: MessageInfo,
+    circuit_id: String,
+    index: usize,
+) -> Result<Response, ContractError> {
+    check_admin(deps.as_ref(), &info)?;
+
+    let mut circuit = CIRCUITS.load(deps.storage, &circuit_id)?;
+    let version = circuit.active_version.unwrap_or(circuit.latest_version);
+    let registration = KEY_REGISTRATIONS.load(deps.storage, (&circuit_id, version))?;
+    if index >= registration.num_public_inputs as usize {
+        return Err(ContractError::InvalidMsgsHashIndex { index });
+    }
+    circuit.msgs_hash_index = Some(index);
+    CIRCUITS.save(deps.storage, &circuit_id, &circuit)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_msgs_hash_input")
+        .add_attribute("circuit_id", circuit_id)
+        .add_attribute("index", index.to_string()))
+}
+
+fn check_input_name_count(input_names: &InputNames, num_public_inputs: u32) -> Result<(), ContractError> {
+    let actual = input_names.names().len();
+    if actual != num_public_inputs as usize {
+        return Err(ContractError::InputNamesMismatch { expected: num_public_inputs as usize, actual });
+    }
+    Ok(())
+}
+
+fn load_key_status(deps: Deps, circuit_id: &str, version: u64) -> Result<KeyStatus, ContractError> {
+    KEY_STATUSES.may_load(deps.storage, (circuit_id, version))?.ok_or_else(|| ContractError::UnknownKeyVersion {
+        circuit_id: circuit_id.to_string(),
+        version,
+    })
+}
+
+// Leaves the circuit without an active key if version was it
+fn clear_active_version(deps: DepsMut, circuit_id: &str, version: u64) -> Result<(), ContractError> {
+    let mut circuit = CIRCUITS.load(deps.storage, circuit_id)?;
+    if circuit.active_version == Some(version) {
+        circuit.active_version = None;
+        CIRCUITS.save(deps.storage, circuit_id, &circuit)?;
+    }
+    Ok(())
+}
+
+// The active key and the most recently deprecated one, enough for proofs made just before a rotation
+const MAX_VERSIONS_TRIED: usize = 2;
+
+// Key versions a proof is checked against, in order: only the requested one, or else the active
+// key followed by the deprecated keys still accepted, newest first. Never empty, and without a
+// requested version at most MAX_VERSIONS_TRIED long, so that the pairing checks a proof costs do
+// not grow with the number of keys. Older keys that are still accepted need key_version.
+pub(super) fn candidate_versions(
+    deps: Deps,
+    env: &Env,
+    circuit_id: &str,
+    requested: Option<u64>,
+) -> Result<Vec<u64>, ContractError> {
+    let circuit = CIRCUITS.load(deps.storage, circuit_id)?;
+    if let Some(version) = requested {
+        if !load_key_status(deps, circuit_id, version)?.is_usable(&env.block) {
+            return Err(ContractError::KeyVersionNotUsable { version });
+        }
+        return Ok(vec![version]);
+    }
+
+    let mut versions: Vec<u64> = circuit.active_version.into_iter().collect();
+    for item in KEY_STATUSES.prefix(circuit_id).range(deps.storage, None, None, Order::Descending) {
+        if versions.len() == MAX_VERSIONS_TRIED {
+            break;
+        }
+        let (version, status) = item?;
+        if matches!(status, KeyStatus::Deprecated { .. }) && status.is_usable(&env.block) {
+            versions.push(version);
+        }
+    }
+    if versions.is_empty() {
+        return Err(ContractError::NoUsableKey { circuit_id: circuit_id.to_string() });
+    }
+    Ok(versions)
+}
+
+// Keeps the versions whose key has the expected digest, which must leave at least one
+pub(super) fn pin_key_hash(
+    deps: Deps,
+    circuit_id: &str,
+    versions: Vec<u64>,
+    expected: &str,
+) -> Result<Vec<u64>, ContractError> {
+    let expected = expected.strip_prefix("0x").unwrap_or(expected).to_lowercase();
+    let mut pinned = Vec::new();
+    let mut first_hash = None;
+    for version in versions {
+        let key_hash = KEY_REGISTRATIONS.load(deps.storage, (circuit_id, version))?.key_hash;
+        if key_hash == expected {
+            pinned.push(version);
+        }
+        first_hash.get_or_insert(key_hash);
+    }
+
+    if pinned.is_empty() {
+        return Err(ContractError::VkeyHashMismatch { expected, actual: first_hash.unwrap_or_default() });
+    }
+    Ok(pinned)
+}
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+pub(super) fn list_circuits(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<CircuitsResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let circuits = CIRCUITS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (id, circuit) = item?;
+            circuit_response(deps, id, circuit)
+        })
+        .collect::<Result<Vec<_>, ContractError>>()?;
+
+    Ok(CircuitsResponse { circuits })
+}
+
+fn circuit_response(deps: Deps, id: String, circuit: CircuitInfo) -> Result<CircuitResponse, ContractError> {
+    let key_version = circuit.active_version.unwrap_or(circuit.latest_version);
+    let status = KEY_STATUSES.load(deps.storage, (&id, key_version))?;
+    let registration = KEY_REGISTRATIONS.load(deps.storage, (&id, key_version))?;
+
+    Ok(CircuitResponse {
+        id,
+        active_version: circuit.active_version,
+        proof_system: circuit.proof_system,
+        key_version,
+        status,
+        num_public_inputs: registration.num_public_inputs,
+        curve: registration.curve,
+        key_hash: registration.key_hash,
+        registered_height: registration.registered_height,
+        registrant: registration.registrant,
+        input_names: circuit.input_names.as_ref().map(InputNames::names).unwrap_or_default(),
+        template: match circuit.input_names {
+            Some(InputNames::Template(template)) => Some(template),
+            _ => None,
+        },
+    })
+}
+
+pub(super) fn list_key_versions(
+    deps: Deps,
+    env: Env,
+    circuit_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> Result<KeyVersionsResponse, ContractError> {
+    let circuit = CIRCUITS.load(deps.storage, &circuit_id)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let versions = KEY_STATUSES
+        .prefix(&circuit_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (version, status) = item?;
+            let registration = KEY_REGISTRATIONS.load(deps.storage, (&circuit_id, version))?;
+            Ok(KeyVersionInfo {
+                version,
+                usable: status.is_usable(&env.block),
+                status,
+                key_hash: registration.key_hash,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(KeyVersionsResponse {
+        circuit_id,
+        active_version: circuit.active_version,
+        versions,
+    })
+}
+
+// Returns the prepared form of the key along with its digest
+pub(super) fn prepare_key(vkey: &VkeyStr) -> Result<(Vec<u8>, [u8; 32]), ContractError> {
+    match vkey.curve {
+        Curve::Bls12_381 => {
+            let parsed_vkey = parse_verification_key(vkey)?;
+            Ok((PreparedVerifyingKey::new(&parsed_vkey).to_bytes(), verifying_key_digest(&parsed_vkey)))
+        }
+        Curve::Bn254 => {
+            let parsed_vkey = bn254::parse_verification_key(vkey)?;
+            Ok((bn254::prepared_key_bytes(&parsed_vkey)?, bn254::verifying_key_digest(vkey)))
+        }
+    }
+}
+
+pub(super) fn query_circuit(deps: Deps, id: String) -> Result<CircuitResponse, ContractError> {
+    let circuit = CIRCUITS.load(deps.storage, &id)?;
+    circuit_response(deps, id, circuit)
+}
+
+pub(super) fn query_circuit_templates() -> CircuitTemplatesResponse {
+    let templates = CircuitTemplate::ALL
+        .iter()
+        .map(|template| CircuitTemplateInfo {
+            template: *template,
+            input_names: template.input_names(),
+            curve: template.curve(),
+            proof_system: ProofSystem::Groth16,
+        })
+        .collect();
+    CircuitTemplatesResponse { templates }
+}
@@ -0,0 +1,103 @@
+use cosmwasm_std::{CosmosMsg, DepsMut, Env, Response};
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+use crate::msg::MsgsHashResponse;
+use crate::state::{CIRCUITS, NULLIFIERS, NullifierSource, PublicInput, msgs_hash, public_input_le_bytes};
+
+use super::keys::candidate_versions;
+use super::verify::verify_against_versions;
+
+// Proofs accepted once: each records a nullifier, and verify_and_execute also runs the messages the
+// proof commits to
+
+pub(super) fn submit_proof(
+    mut deps: DepsMut,
+    env: Env,
+    circuit_id: String,
+    proof: String,
+    public_inputs: Vec<PublicInput>,
+    key_version: Option<u64>,
+) -> Result<Response, ContractError> {
+    let source = CIRCUITS
+        .load(deps.storage, &circuit_id)?
+        .nullifier
+        .ok_or_else(|| ContractError::NoNullifierSource { circuit_id: circuit_id.clone() })?;
+    let versions = candidate_versions(deps.as_ref(), &env, &circuit_id, key_version)?;
+    let (version, proof_bytes) = verify_against_versions(deps.as_ref(), &circuit_id, &versions, &proof, &public_inputs)?;
+    let nullifier = use_nullifier(deps.branch(), &env, &circuit_id, source, &proof_bytes, &public_inputs)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "submit_proof")
+        .add_attribute("circuit_id", circuit_id)
+        .add_attribute("nullifier", hex::encode(&nullifier))
+        .add_attribute("key_version", version.to_string()))
+}
+
+// Records the nullifier of a proof that verified against public_inputs, refusing one already
+// recorded for the circuit
+fn use_nullifier(
+    deps: DepsMut,
+    env: &Env,
+    circuit_id: &str,
+    source: NullifierSource,
+    proof_bytes: &[u8],
+    public_inputs: &[PublicInput],
+) -> Result<Vec<u8>, ContractError> {
+    let nullifier = match source {
+        NullifierSource::ProofHash => Sha256::digest(proof_bytes).to_vec(),
+        // Verification already rejected inputs that are not field elements, so this is canonical
+        NullifierSource::PublicInput { index } => {
+            let input = public_inputs.get(index).ok_or(ContractError::InvalidNullifierIndex { index })?;
+            public_input_le_bytes(input).ok_or(ContractError::InvalidPublicInput { index })?.to_vec()
+        }
+    };
+    if NULLIFIERS.has(deps.storage, (circuit_id, &nullifier)) {
+        return Err(ContractError::NullifierAlreadyUsed { nullifier: hex::encode(&nullifier) });
+    }
+    NULLIFIERS.save(deps.storage, (circuit_id, &nullifier), &env.block.height)?;
+    Ok(nullifier)
+}
+
+// Anyone holding a valid proof could otherwise send the contract's funds anywhere, and as often as
+// they like. The messages hash input ties the proof to msgs, the nullifier to a single execution.
+pub(super) fn verify_and_execute(
+    mut deps: DepsMut,
+    env: Env,
+    circuit_id: String,
+    proof: String,
+    public_inputs: Vec<PublicInput>,
+    msgs: Vec<CosmosMsg>,
+    key_version: Option<u64>,
+) -> Result<Response, ContractError> {
+    let circuit = CIRCUITS.load(deps.storage, &circuit_id)?;
+    let source = match circuit.nullifier {
+        Some(NullifierSource::ProofHash) => return Err(ContractError::ReplayableNullifier { circuit_id }),
+        Some(source) => source,
+        None => return Err(ContractError::NoNullifierSource { circuit_id }),
+    };
+    let index = circuit.msgs_hash_index.ok_or_else(|| ContractError::NoMsgsHashInput { circuit_id: circuit_id.clone() })?;
+    let expected = msgs_hash(&msgs)?;
+    let committed = public_inputs.get(index).and_then(public_input_le_bytes).map(|mut bytes| {
+        bytes.reverse();
+        bytes
+    });
+    if committed != Some(expected) {
+        return Err(ContractError::MsgsHashMismatch { expected: hex::encode(expected) });
+    }
+
+    let versions = candidate_versions(deps.as_ref(), &env, &circuit_id, key_version)?;
+    let (version, proof_bytes) = verify_against_versions(deps.as_ref(), &circuit_id, &versions, &proof, &public_inputs)?;
+    let nullifier = use_nullifier(deps.branch(), &env, &circuit_id, source, &proof_bytes, &public_inputs)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "verify_and_execute")
+        .add_attribute("circuit_id", circuit_id)
+        .add_attribute("nullifier", hex::encode(&nullifier))
+        .add_attribute("key_version", version.to_string())
+        .add_messages(msgs))
+}
+
+pub(super) fn query_msgs_hash(msgs: &[CosmosMsg]) -> Result<MsgsHashResponse, ContractError> {
+    Ok(MsgsHashResponse { msgs_hash: PublicInput::HexBe(hex::encode(msgs_hash(msgs)?)) })
+}
@@ -0,0 +1,74 @@
+use cosmwasm_std::{Addr, Storage};
+use super::*;
+use crate::msg::ConfigResponse;
+
+#[test]
+fn test_admin_management() {
+    let mut fixture = Fixture::new();
+    let config = |fixture: &Fixture| -> ConfigResponse { fixture.query(QueryMsg::Config {}).unwrap() };
+    assert_eq!(config(&fixture), ConfigResponse { admin_address: Some(fixture.addr(ADMIN)), pending_admin: None });
+
+    // Only the admin manages the admin, and nobody can accept before a proposal
+    for msg in [
+        ExecuteMsg::UpdateAdmin { admin_address: fixture.addr("addr2").to_string() },
+        ExecuteMsg::ProposeAdmin { admin_address: fixture.addr("addr2").to_string() },
+        ExecuteMsg::RenounceAdmin {},
+    ] {
+        let err = fixture.execute("addr2", msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+    let err = fixture.execute("addr2", ExecuteMsg::AcceptAdmin {}).unwrap_err();
+    assert!(matches!(err, ContractError::NoPendingAdmin {}));
+
+    let update_msg = ExecuteMsg::UpdateAdmin { admin_address: fixture.addr("addr2").to_string() };
+    let _ = fixture.execute(ADMIN, update_msg).unwrap();
+    assert_eq!(config(&fixture).admin_address, Some(fixture.addr("addr2")));
+    let register_msg = register_msg("multiply", serde_json::from_str(DEMO_VKEY).unwrap());
+    let err = fixture.execute(ADMIN, register_msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let _ = fixture.execute("addr2", register_msg.clone()).unwrap();
+
+    // The proposed admin takes over only once it accepts, and nobody else can accept for it
+    let propose_msg = ExecuteMsg::ProposeAdmin { admin_address: fixture.addr("addr3").to_string() };
+    let _ = fixture.execute("addr2", propose_msg).unwrap();
+    assert_eq!(
+        config(&fixture),
+        ConfigResponse { admin_address: Some(fixture.addr("addr2")), pending_admin: Some(fixture.addr("addr3")) }
+    );
+    let err = fixture.execute(ADMIN, ExecuteMsg::AcceptAdmin {}).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let response = fixture.execute("addr3", ExecuteMsg::AcceptAdmin {}).unwrap();
+    assert_eq!(response.attributes[1].value, fixture.addr("addr3").as_str());
+    assert_eq!(config(&fixture), ConfigResponse { admin_address: Some(fixture.addr("addr3")), pending_admin: None });
+    let err = fixture.execute("addr3", ExecuteMsg::AcceptAdmin {}).unwrap_err();
+    assert!(matches!(err, ContractError::NoPendingAdmin {}));
+
+    // Updating the admin drops a pending proposal
+    let propose_msg = ExecuteMsg::ProposeAdmin { admin_address: fixture.addr("addr4").to_string() };
+    let _ = fixture.execute("addr3", propose_msg).unwrap();
+    let update_msg = ExecuteMsg::UpdateAdmin { admin_address: fixture.addr("addr3").to_string() };
+    let _ = fixture.execute("addr3", update_msg).unwrap();
+    let err = fixture.execute("addr4", ExecuteMsg::AcceptAdmin {}).unwrap_err();
+    assert!(matches!(err, ContractError::NoPendingAdmin {}));
+
+    // After renouncing, every admin only message is refused
+    let _ = fixture.execute("addr3", ExecuteMsg::RenounceAdmin {}).unwrap();
+    assert_eq!(config(&fixture), ConfigResponse { admin_address: None, pending_admin: None });
+    for msg in [
+        register_msg,
+        ExecuteMsg::RevokeVerifyingKey { circuit_id: "multiply".to_string(), version: 1 },
+        ExecuteMsg::UpdateAdmin { admin_address: fixture.addr("addr3").to_string() },
+        ExecuteMsg::RenounceAdmin {},
+    ] {
+        let err = fixture.execute("addr3", msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+}
+
+#[test]
+fn test_config_saved_before_admin_management() {
+    let mut deps = mock_dependencies();
+    deps.storage.set(b"state", br#"{"admin_address":"addr1"}"#);
+    let config: ConfigResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config, ConfigResponse { admin_address: Some(Addr::unchecked("addr1")), pending_admin: None });
+}
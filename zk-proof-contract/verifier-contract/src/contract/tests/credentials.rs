@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+
+use ark_groth16::Groth16;
+use ark_relations::lc;
+use ark_relations::r1cs::{self, ConstraintSynthesizer, ConstraintSystemRef};
+use ark_snark::SNARK;
+use super::*;
+use crate::msg::{CircuitResponse, CircuitTemplatesResponse};
+use crate::semaphore;
+use crate::snarkjs::SnarkjsVkey;
+use crate::state::{CircuitTemplate, InputNames, ProofSystem};
+
+// Arkworks rewrite of testdata/templates/range.circom and comparison.circom, with the public inputs
+// in the same order. Written by hand rather than compiled from the circom sources, so it tests the
+// contract's input names, not the circom circuits; test_snarkjs_template_fixtures checks those. A
+// value out of bounds leaves the constraints unsatisfied
+struct CredentialCircuit {
+    template: CircuitTemplate,
+    value: u64,
+    salt: ark_bn254::Fr,
+    // min and max for Range, threshold for Comparison
+    bounds: Vec<u64>,
+}
+
+impl CredentialCircuit {
+    fn commitment(&self) -> ark_bn254::Fr {
+        semaphore::poseidon2(&mut semaphore::hasher(), ark_bn254::Fr::from(self.value), self.salt)
+    }
+
+    fn public_inputs(&self) -> BTreeMap<String, PublicInput> {
+        let mut values = vec![self.commitment()];
+        values.extend(self.bounds.iter().map(|bound| ark_bn254::Fr::from(*bound)));
+        self.template.input_names().into_iter().zip(values).map(|(name, value)| (name, bn254_decimal(value))).collect()
+    }
+}
+
+impl ConstraintSynthesizer<ark_bn254::Fr> for CredentialCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<ark_bn254::Fr>) -> r1cs::Result<()> {
+        let commitment = cs.new_input_variable(|| Ok(self.commitment()))?;
+        let mut bounds = Vec::new();
+        for bound in self.bounds.iter() {
+            bounds.push((cs.new_input_variable(|| Ok(ark_bn254::Fr::from(*bound)))?, ark_bn254::Fr::from(*bound)));
+        }
+        let value = ark_bn254::Fr::from(self.value);
+        let value_var = cs.new_witness_variable(|| Ok(value))?;
+        let salt_var = cs.new_witness_variable(|| Ok(self.salt))?;
+
+        let (hash, _) = poseidon_gadget(&cs, [(lc!() + value_var, value), (lc!() + salt_var, self.salt)])?;
+        cs.enforce_constraint(lc!() + hash, lc!() + r1cs::Variable::One, lc!() + commitment)?;
+
+        // value - lower and upper - value must both fit in 64 bits, as circomlib's comparators check
+        let (lower, lower_value) = bounds[0];
+        enforce_u64(&cs, lc!() + value_var - lower, value - lower_value)?;
+        if self.template == CircuitTemplate::Range {
+            let (upper, upper_value) = bounds[1];
+            enforce_u64(&cs, lc!() + upper - value_var, upper_value - value)?;
+        }
+        Ok(())
+    }
+}
+
+fn bn254_decimal(value: ark_bn254::Fr) -> PublicInput {
+    PublicInput::Decimal(value.into_bigint().to_string())
+}
+
+// circomlib's Poseidon for two inputs, with light-poseidon's constants. Returns the hash as a
+// variable along with its value
+fn poseidon_gadget(
+    cs: &ConstraintSystemRef<ark_bn254::Fr>,
+    inputs: [(r1cs::LinearCombination<ark_bn254::Fr>, ark_bn254::Fr); 2],
+) -> r1cs::Result<(r1cs::Variable, ark_bn254::Fr)> {
+    let params = light_poseidon::parameters::bn254_x5::get_poseidon_parameters::<ark_bn254::Fr>(3).unwrap();
+    let half_full_rounds = params.full_rounds / 2;
+    let mut state = vec![(lc!(), ark_bn254::Fr::from(0u64))];
+    state.extend(inputs);
+    let mut hash = None;
+    for round in 0..params.full_rounds + params.partial_rounds {
+        for (i, (lc, value)) in state.iter_mut().enumerate() {
+            let constant = params.ark[round * params.width + i];
+            *lc = lc.clone() + (constant, r1cs::Variable::One);
+            *value += constant;
+        }
+        let full_round = round < half_full_rounds || round >= half_full_rounds + params.partial_rounds;
+        let sbox_width = if full_round { params.width } else { 1 };
+        for (lc, value) in state.iter_mut().take(sbox_width) {
+            let (variable, power) = pow5(cs, lc.clone(), *value)?;
+            *lc = lc!() + variable;
+            *value = power;
+        }
+
+        let mut mixed = Vec::new();
+        for row in params.mds.iter() {
+            let mut lc = lc!();
+            let mut value = ark_bn254::Fr::from(0u64);
+            for ((element, element_value), coefficient) in state.iter().zip(row.iter()) {
+                lc = lc + (element.clone() * *coefficient);
+                value += *element_value * coefficient;
+            }
+            // A new variable per element keeps the linear combinations from growing every round
+            let variable = cs.new_witness_variable(|| Ok(value))?;
+            cs.enforce_constraint(lc, lc!() + r1cs::Variable::One, lc!() + variable)?;
+            mixed.push((variable, value));
+        }
+        hash = Some(mixed[0]);
+        state = mixed.into_iter().map(|(variable, value)| (lc!() + variable, value)).collect();
+    }
+    Ok(hash.expect("at least one round"))
+}
+
+// x^5 in three constraints
+fn pow5(
+    cs: &ConstraintSystemRef<ark_bn254::Fr>,
+    x: r1cs::LinearCombination<ark_bn254::Fr>,
+    value: ark_bn254::Fr,
+) -> r1cs::Result<(r1cs::Variable, ark_bn254::Fr)> {
+    let square = cs.new_witness_variable(|| Ok(value * value))?;
+    cs.enforce_constraint(x.clone(), x.clone(), lc!() + square)?;
+    let fourth = cs.new_witness_variable(|| Ok(value * value * value * value))?;
+    cs.enforce_constraint(lc!() + square, lc!() + square, lc!() + fourth)?;
+    let fifth_value = value * value * value * value * value;
+    let fifth = cs.new_witness_variable(|| Ok(fifth_value))?;
+    cs.enforce_constraint(lc!() + fourth, x, lc!() + fifth)?;
+    Ok((fifth, fifth_value))
+}
+
+// circomlib's Num2Bits(64): x is the sum of 64 boolean variables
+fn enforce_u64(
+    cs: &ConstraintSystemRef<ark_bn254::Fr>,
+    x: r1cs::LinearCombination<ark_bn254::Fr>,
+    value: ark_bn254::Fr,
+) -> r1cs::Result<()> {
+    let low_limb = value.into_bigint().0[0];
+    let mut sum = lc!();
+    for i in 0..64 {
+        let bit = cs.new_witness_variable(|| Ok(ark_bn254::Fr::from((low_limb >> i) & 1)))?;
+        cs.enforce_constraint(lc!() + bit, lc!() + bit, lc!() + bit)?;
+        sum += (ark_bn254::Fr::from(1u128 << i), bit);
+    }
+    cs.enforce_constraint(sum, lc!() + r1cs::Variable::One, x)
+}
+
+fn verify(
+    fixture: &Fixture,
+    circuit_id: &str,
+    proof: &str,
+    public_inputs: Vec<PublicInput>,
+    named_inputs: BTreeMap<String, PublicInput>,
+) -> Result<VerifyProofResponse, ContractError> {
+    fixture.query(QueryMsg::VerifyProof {
+        circuit_id: circuit_id.to_string(),
+        proof: proof.to_string(),
+        public_inputs,
+        key_version: None,
+        expected_vkey_hash: None,
+        named_inputs: Some(named_inputs),
+    })
+}
+
+// The verification_key.json snarkjs would export for vk
+fn bn254_snarkjs_vkey(vk: &ark_groth16::VerifyingKey<Bn254>) -> SnarkjsVkey {
+    let g1 = |point: &ark_bn254::G1Affine| {
+        vec![point.x.into_bigint().to_string(), point.y.into_bigint().to_string(), "1".to_string()]
+    };
+    let g2 = |point: &ark_bn254::G2Affine| {
+        vec![
+            vec![point.x.c0.into_bigint().to_string(), point.x.c1.into_bigint().to_string()],
+            vec![point.y.c0.into_bigint().to_string(), point.y.c1.into_bigint().to_string()],
+            vec!["1".to_string(), "0".to_string()],
+        ]
+    };
+    SnarkjsVkey {
+        protocol: "groth16".to_string(),
+        curve: "bn128".to_string(),
+        n_public: vk.gamma_abc_g1.len() - 1,
+        vk_alpha_1: g1(&vk.alpha_g1),
+        vk_beta_2: g2(&vk.beta_g2),
+        vk_gamma_2: g2(&vk.gamma_g2),
+        vk_delta_2: g2(&vk.delta_g2),
+        ic: vk.gamma_abc_g1.iter().map(g1).collect(),
+    }
+}
+
+fn register_template_msg(circuit_id: &str, template: CircuitTemplate, vkey: SnarkjsVkey) -> ExecuteMsg {
+    ExecuteMsg::RegisterTemplateKey { circuit_id: circuit_id.to_string(), template, vkey, grace_period: None }
+}
+
+#[test]
+fn test_credential_circuits_check_bounds() {
+    let salt = ark_bn254::Fr::from(987654321u64);
+    for (template, value, bounds, satisfied) in [
+        (CircuitTemplate::Comparison, 30, vec![18], true),
+        (CircuitTemplate::Comparison, 18, vec![18], true),
+        (CircuitTemplate::Comparison, 17, vec![18], false),
+        (CircuitTemplate::Range, 5_000, vec![1_000, 10_000], true),
+        (CircuitTemplate::Range, 10_001, vec![1_000, 10_000], false),
+    ] {
+        let cs = r1cs::ConstraintSystem::<ark_bn254::Fr>::new_ref();
+        let circuit = CredentialCircuit { template, value, salt, bounds };
+        let commitment = circuit.commitment();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert_eq!(cs.is_satisfied().unwrap(), satisfied, "{:?} of {}", template, value);
+        // The first instance entry is the constant one
+        assert_eq!(cs.borrow().unwrap().instance_assignment[1], commitment);
+    }
+}
+
+#[test]
+fn test_named_public_inputs() {
+    let mut fixture = Fixture::new();
+    let salt = ark_bn254::Fr::from(987654321u64);
+    let adult = CredentialCircuit { template: CircuitTemplate::Comparison, value: 30, salt, bounds: vec![18] };
+    let minor = CredentialCircuit { template: CircuitTemplate::Comparison, value: 17, salt, bounds: vec![17] };
+    let balance = CredentialCircuit {
+        template: CircuitTemplate::Range,
+        value: 5_000,
+        salt,
+        bounds: vec![1_000, 10_000],
+    };
+    let blank_adult = CredentialCircuit { template: CircuitTemplate::Comparison, value: 0, salt, bounds: vec![0] };
+    let (adult_pk, adult_vk) = Groth16::<Bn254>::circuit_specific_setup(blank_adult, &mut OsRng).unwrap();
+    let blank_balance = CredentialCircuit { template: CircuitTemplate::Range, value: 0, salt, bounds: vec![0, 0] };
+    let (balance_pk, balance_vk) = Groth16::<Bn254>::circuit_specific_setup(blank_balance, &mut OsRng).unwrap();
+
+    let adult_inputs = adult.public_inputs();
+    let mut minor_inputs = minor.public_inputs();
+    minor_inputs.insert("threshold".to_string(), decimal(18));
+    let balance_inputs = balance.public_inputs();
+    let adult_proof = bn254_proof_str(&Groth16::<Bn254>::prove(&adult_pk, adult, &mut OsRng).unwrap());
+    let minor_proof = bn254_proof_str(&Groth16::<Bn254>::prove(&adult_pk, minor, &mut OsRng).unwrap());
+    let balance_proof = bn254_proof_str(&Groth16::<Bn254>::prove(&balance_pk, balance, &mut OsRng).unwrap());
+
+    fixture.register("adult", bn254_vkey_str(&adult_vk));
+    fixture.register("balance", bn254_vkey_str(&balance_vk));
+
+    let err = verify(&fixture, "adult", &adult_proof, vec![], adult_inputs.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NoInputNames { .. }));
+
+    let names_msg = |circuit_id: &str, input_names: InputNames| ExecuteMsg::SetInputNames {
+        circuit_id: circuit_id.to_string(),
+        input_names,
+    };
+    let template_msg = |circuit_id: &str, template: CircuitTemplate| names_msg(circuit_id, InputNames::Template(template));
+    let err = fixture.execute("addr2", template_msg("adult", CircuitTemplate::Comparison)).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let err = fixture.execute(ADMIN, template_msg("adult", CircuitTemplate::Range)).unwrap_err();
+    assert!(matches!(err, ContractError::InputNamesMismatch { expected: 2, actual: 3 }));
+    let duplicate_names = InputNames::Custom(vec!["commitment".to_string(), "commitment".to_string()]);
+    let err = fixture.execute(ADMIN, names_msg("adult", duplicate_names)).unwrap_err();
+    assert!(matches!(err, ContractError::DuplicateInputName { .. }));
+    for (circuit_id, template) in [("adult", CircuitTemplate::Comparison), ("balance", CircuitTemplate::Range)] {
+        let _ = fixture.execute(ADMIN, template_msg(circuit_id, template)).unwrap();
+    }
+
+    // Named inputs land in circuit order
+    let response = verify(&fixture, "adult", &adult_proof, vec![], adult_inputs.clone()).unwrap();
+    assert!(response.valid);
+    assert_eq!(response.public_inputs, vec![adult_inputs["commitment"].clone(), adult_inputs["threshold"].clone()]);
+    let response = verify(&fixture, "balance", &balance_proof, vec![], balance_inputs.clone()).unwrap();
+    assert!(response.valid);
+    assert_eq!(response.public_inputs[1], bn254_decimal(ark_bn254::Fr::from(1_000u64)));
+
+    // A proof of age >= 17 does not pass for age >= 18, nor one of age >= 18 for age >= 16
+    let response = verify(&fixture, "adult", &minor_proof, vec![], minor_inputs).unwrap();
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+    let mut lowered = adult_inputs.clone();
+    lowered.insert("threshold".to_string(), decimal(16));
+    let response = verify(&fixture, "adult", &adult_proof, vec![], lowered).unwrap();
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+
+    let mut missing = balance_inputs.clone();
+    missing.remove("max");
+    let err = verify(&fixture, "balance", &balance_proof, vec![], missing).unwrap_err();
+    assert!(matches!(err, ContractError::MissingInputName { name } if name == "max"));
+    let mut unknown = adult_inputs.clone();
+    unknown.insert("age".to_string(), decimal(30));
+    let err = verify(&fixture, "adult", &adult_proof, vec![], unknown).unwrap_err();
+    assert!(matches!(err, ContractError::UnknownInputName { name } if name == "age"));
+    let err = verify(&fixture, "adult", &adult_proof, vec![decimal(18)], adult_inputs).unwrap_err();
+    assert!(matches!(err, ContractError::MixedPublicInputs {}));
+
+    let circuit: CircuitResponse = fixture.query(QueryMsg::Circuit { id: "balance".to_string() }).unwrap();
+    assert_eq!(circuit.template, Some(CircuitTemplate::Range));
+    assert_eq!(circuit.input_names, vec!["commitment", "min", "max"]);
+    let templates: CircuitTemplatesResponse = fixture.query(QueryMsg::CircuitTemplates {}).unwrap();
+    assert_eq!(templates.templates.len(), 2);
+
+    // Later keys must keep the named input count
+    let err = fixture.execute(ADMIN, register_msg("balance", bn254_vkey_str(&adult_vk))).unwrap_err();
+    assert!(matches!(err, ContractError::InputNamesMismatch { expected: 2, actual: 3 }));
+}
+
+#[test]
+fn test_register_template_key() {
+    let mut fixture = Fixture::new();
+    let salt = ark_bn254::Fr::from(987654321u64);
+    let adult = CredentialCircuit { template: CircuitTemplate::Comparison, value: 30, salt, bounds: vec![18] };
+    let blank = CredentialCircuit { template: CircuitTemplate::Comparison, value: 0, salt, bounds: vec![0] };
+    let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(blank, &mut OsRng).unwrap();
+    let inputs = adult.public_inputs();
+    let proof = bn254_proof_str(&Groth16::<Bn254>::prove(&pk, adult, &mut OsRng).unwrap());
+    let vkey = bn254_snarkjs_vkey(&vk);
+
+    let msg = register_template_msg("adult", CircuitTemplate::Comparison, vkey.clone());
+    let err = fixture.execute("addr2", msg).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let err = fixture.execute(ADMIN, register_template_msg("adult", CircuitTemplate::Range, vkey.clone())).unwrap_err();
+    assert!(matches!(err, ContractError::InputNamesMismatch { expected: 2, actual: 3 }));
+    let err = fixture.query::<CircuitResponse>(QueryMsg::Circuit { id: "adult".to_string() }).unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+
+    // One message registers the key and names its inputs
+    let response = fixture.execute(ADMIN, register_template_msg("adult", CircuitTemplate::Comparison, vkey)).unwrap();
+    assert!(response.attributes.iter().any(|attr| attr.key == "template" && attr.value == "comparison"));
+    let circuit: CircuitResponse = fixture.query(QueryMsg::Circuit { id: "adult".to_string() }).unwrap();
+    assert_eq!(circuit.template, Some(CircuitTemplate::Comparison));
+    assert!(verify(&fixture, "adult", &proof, vec![], inputs).unwrap().valid);
+
+    let templates: CircuitTemplatesResponse = fixture.query(QueryMsg::CircuitTemplates {}).unwrap();
+    for info in templates.templates {
+        assert_eq!((info.curve, info.proof_system), (Curve::Bn254, ProofSystem::Groth16));
+    }
+}
+
+// The tests above prove with the arkworks rewrite. This one checks keys and proofs snarkjs made from
+// the circom sources. The fixtures are not committed yet: run testdata/templates/generate.sh, commit
+// its output and drop the ignore
+#[test]
+#[ignore = "needs the snarkjs fixtures from testdata/templates/generate.sh"]
+fn test_snarkjs_template_fixtures() {
+    let mut fixture = Fixture::new();
+    let read_fixture = |name: String| {
+        let path = format!("{}/testdata/templates/{}", env!("CARGO_MANIFEST_DIR"), name);
+        std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("cannot read {} ({})", path, err))
+    };
+    // generate.sh commits to value 30 with this salt, so circomlib's Poseidon has to agree with ours
+    let salt = ark_bn254::Fr::from(987654321u64);
+    let commitment = CredentialCircuit { template: CircuitTemplate::Comparison, value: 30, salt, bounds: vec![] }.commitment();
+
+    for (template, bounds) in [(CircuitTemplate::Range, vec![18, 65]), (CircuitTemplate::Comparison, vec![18])] {
+        let name = template.name();
+        let vkey: SnarkjsVkey = serde_json::from_str(&read_fixture(format!("{}_verification_key.json", name))).unwrap();
+        let proof = read_fixture(format!("{}_proof.json", name));
+        let public: Vec<String> = serde_json::from_str(&read_fixture(format!("{}_public.json", name))).unwrap();
+        let mut expected = vec![commitment.into_bigint().to_string()];
+        expected.extend(bounds.iter().map(|bound: &u64| bound.to_string()));
+        assert_eq!(public, expected, "{}", name);
+
+        let _ = fixture.execute(ADMIN, register_template_msg(name, template, vkey)).unwrap();
+
+        let inputs: BTreeMap<String, PublicInput> =
+            template.input_names().into_iter().zip(public.into_iter().map(PublicInput::Decimal)).collect();
+        let response = verify(&fixture, name, &proof, vec![], inputs.clone()).unwrap();
+        assert!(response.valid, "{}", name);
+
+        let mut raised = inputs;
+        raised.insert(template.input_names()[1].clone(), decimal(19));
+        let response = verify(&fixture, name, &proof, vec![], raised).unwrap();
+        assert_eq!(response.failure, Some(VerificationFailure::PairingFailure), "{}", name);
+    }
+}
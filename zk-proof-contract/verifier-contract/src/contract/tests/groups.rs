@@ -0,0 +1,212 @@
+use cosmwasm_std::Uint256;
+use ff::PrimeField;
+use ark_groth16::Groth16;
+use ark_relations::lc;
+use ark_relations::r1cs::{self, ConstraintSynthesizer, ConstraintSystemRef};
+use ark_snark::SNARK;
+use super::*;
+use crate::msg::{GroupResponse, MerkleTreeResponse};
+use crate::semaphore;
+
+#[test]
+fn test_merkle_membership_proof() {
+    let mut fixture = Fixture::new();
+
+    // Stands in for a membership circuit: the second public input plays the root
+    let params = setup_multiply_demo(2);
+    fixture.register("membership", to_vkey_str(&params));
+    let proof = prove_multiply_demo(&params, &[(Scalar::from(3), Scalar::from(4)), (Scalar::from(5), Scalar::from(7))]);
+
+    let owner = fixture.addr("owner");
+    let create_msg = |root_index: usize, history_size: u32| ExecuteMsg::CreateMerkleTree {
+        tree_id: "allowlist".to_string(),
+        circuit_id: "membership".to_string(),
+        root_index,
+        owner: Some(owner.to_string()),
+        history_size,
+    };
+    let err = fixture.execute("owner", create_msg(1, 2)).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let err = fixture.execute(ADMIN, create_msg(2, 2)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidRootIndex { index: 2 }));
+    let err = fixture.execute(ADMIN, create_msg(1, 0)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidHistorySize { size: 0, .. }));
+    let _ = fixture.execute(ADMIN, create_msg(1, 2)).unwrap();
+    let err = fixture.execute(ADMIN, create_msg(1, 2)).unwrap_err();
+    assert!(matches!(err, ContractError::MerkleTreeExists { .. }));
+
+    let verify = |fixture: &Fixture, proof: &str, public_inputs: Vec<PublicInput>| {
+        fixture.query::<VerifyProofResponse>(QueryMsg::VerifyMembershipProof {
+            tree_id: "allowlist".to_string(),
+            proof: proof.to_string(),
+            public_inputs,
+            key_version: None,
+            expected_vkey_hash: None,
+        })
+    };
+    let err = verify(&fixture, &proof, vec![decimal(12), decimal(35)]).unwrap_err();
+    assert!(matches!(err, ContractError::NoMerkleRoot { .. }));
+
+    // The admin and the owner publish roots, nobody else, and only field elements of the circuit's curve
+    let update_msg = |root: &str| ExecuteMsg::UpdateMerkleRoot {
+        tree_id: "allowlist".to_string(),
+        root: PublicInput::Decimal(root.to_string()),
+    };
+    let err = fixture.execute("addr2", update_msg("35")).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let bls12_381_modulus = "52435875175126190479447740508185965837690552500527637822603658699938581184513";
+    let err = fixture.execute(ADMIN, update_msg(bls12_381_modulus)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMerkleRoot {}));
+    let _ = fixture.execute("owner", update_msg("35")).unwrap();
+    let response = verify(&fixture, &proof, vec![decimal(12), decimal(35)]).unwrap();
+    assert!(response.valid);
+    assert_eq!(response.public_inputs, vec![decimal(12), decimal(35)]);
+
+    // Proofs against a recent root still verify, other roots are refused before any pairing
+    let _ = fixture.execute(ADMIN, update_msg("99")).unwrap();
+    assert!(verify(&fixture, &proof, vec![decimal(12), decimal(35)]).unwrap().valid);
+    let response = verify(&fixture, &proof, vec![decimal(12), decimal(99)]).unwrap();
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+    let err = verify(&fixture, &proof, vec![decimal(12), decimal(7)]).unwrap_err();
+    assert!(matches!(err, ContractError::MerkleRootNotAccepted { .. }));
+    let malformed = verify(&fixture, "{}", vec![decimal(12), decimal(35)]).unwrap();
+    assert!(matches!(malformed.failure, Some(VerificationFailure::ParseError { .. })));
+    let response = verify(&fixture, &proof, vec![decimal(12)]).unwrap();
+    assert_eq!(response.failure, Some(VerificationFailure::InputMismatch { expected: 2, actual: 1 }));
+
+    // Until the root falls out of the history
+    let _ = fixture.execute("owner", update_msg("100")).unwrap();
+    let err = verify(&fixture, &proof, vec![decimal(12), decimal(35)]).unwrap_err();
+    assert!(matches!(err, ContractError::MerkleRootNotAccepted { .. }));
+
+    // A root published again becomes the latest, so the next update drops the one after it
+    let _ = fixture.execute(ADMIN, update_msg("99")).unwrap();
+    let _ = fixture.execute(ADMIN, update_msg("101")).unwrap();
+    let response = verify(&fixture, &proof, vec![decimal(12), decimal(99)]).unwrap();
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+    let err = verify(&fixture, &proof, vec![decimal(12), decimal(100)]).unwrap_err();
+    assert!(matches!(err, ContractError::MerkleRootNotAccepted { .. }));
+
+    let tree: MerkleTreeResponse = fixture.query(QueryMsg::MerkleTree { tree_id: "allowlist".to_string() }).unwrap();
+    assert_eq!(tree.owner, Some(owner));
+    assert_eq!(
+        tree.roots,
+        vec![
+            PublicInput::HexLe(hex::encode(Scalar::from(101).to_repr())),
+            PublicInput::HexLe(hex::encode(Scalar::from(99).to_repr())),
+        ]
+    );
+}
+
+// Stands in for the Semaphore circuit, with its public signals in the same order. Only
+// nullifier_hash = identity_nullifier * external_nullifier_hash is proven, the Merkle path and
+// signal are merely bound to the proof
+struct SemaphoreStandIn {
+    identity_nullifier: Option<ark_bn254::Fr>,
+    public_signals: Option<[ark_bn254::Fr; 4]>,
+}
+
+impl ConstraintSynthesizer<ark_bn254::Fr> for SemaphoreStandIn {
+    fn generate_constraints(self, cs: ConstraintSystemRef<ark_bn254::Fr>) -> r1cs::Result<()> {
+        let missing = || r1cs::SynthesisError::AssignmentMissing;
+        let identity_nullifier = cs.new_witness_variable(|| self.identity_nullifier.ok_or_else(missing))?;
+        let mut inputs = Vec::new();
+        for i in 0..4 {
+            inputs.push(cs.new_input_variable(|| Ok(self.public_signals.ok_or_else(missing)?[i]))?);
+        }
+        cs.enforce_constraint(lc!() + identity_nullifier, lc!() + inputs[3], lc!() + inputs[1])?;
+        // Semaphore squares the signal hash too, so that it takes part in a constraint
+        let signal_hash_squared = cs.new_witness_variable(|| {
+            let signal_hash = self.public_signals.ok_or_else(missing)?[2];
+            Ok(signal_hash * signal_hash)
+        })?;
+        cs.enforce_constraint(lc!() + inputs[2], lc!() + inputs[2], lc!() + signal_hash_squared)
+    }
+}
+
+#[test]
+fn test_poseidon_matches_circomlib() {
+    let mut hasher = semaphore::hasher();
+    let hash = semaphore::poseidon2(&mut hasher, ark_bn254::Fr::from(1u64), ark_bn254::Fr::from(2u64));
+    let mut bytes = semaphore::fr_to_binary(hash).to_vec();
+    bytes.reverse();
+    assert_eq!(hex::encode(bytes), "115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a");
+}
+
+#[test]
+fn test_semaphore_group_signal() {
+    let mut fixture = Fixture::new();
+    let blank = SemaphoreStandIn { identity_nullifier: None, public_signals: None };
+    let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(blank, &mut OsRng).unwrap();
+    fixture.register("semaphore", bn254_vkey_str(&vk));
+    fixture.register("multiply", to_vkey_str(&setup_multiply_demo(4)));
+
+    let group_id = Uint256::from(42u64);
+    let create_msg = |circuit_id: &str, depth: u8| ExecuteMsg::CreateGroup {
+        group_id,
+        circuit_id: circuit_id.to_string(),
+        depth,
+        admin: None,
+        history_size: 2,
+    };
+    // Any sender can create a group, but only over a BN254 circuit with Semaphore's four inputs
+    let err = fixture.execute("dao", create_msg("multiply", 2)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidGroupCircuit { .. }));
+    let err = fixture.execute("dao", create_msg("semaphore", 33)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidGroupDepth { depth: 33, .. }));
+    let _ = fixture.execute("dao", create_msg("semaphore", 2)).unwrap();
+    let err = fixture.execute("dao", create_msg("semaphore", 2)).unwrap_err();
+    assert!(matches!(err, ContractError::GroupExists { .. }));
+
+    // Leaves of the empty tree are the group's zero value
+    let hash = |left, right| semaphore::poseidon2(&mut semaphore::hasher(), left, right);
+    let fr = |value: u64| ark_bn254::Fr::from(value);
+    let zero = semaphore::hash_to_field(group_id);
+    let empty_root = hash(hash(zero, zero), hash(zero, zero));
+    let hex_input = |value: ark_bn254::Fr| PublicInput::HexLe(hex::encode(semaphore::fr_to_binary(value).as_slice()));
+    let load_group = |fixture: &Fixture| -> GroupResponse { fixture.query(QueryMsg::Group { group_id }).unwrap() };
+    assert_eq!(load_group(&fixture).roots, vec![hex_input(empty_root)]);
+    assert_eq!(load_group(&fixture).admin, fixture.addr("dao"));
+
+    let add_msg = |commitment: u64| ExecuteMsg::AddMember { group_id, identity_commitment: decimal(commitment) };
+    let err = fixture.execute(ADMIN, add_msg(1)).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    for commitment in 1..=3 {
+        let _ = fixture.execute("dao", add_msg(commitment)).unwrap();
+    }
+    let root = hash(hash(fr(1), fr(2)), hash(fr(3), zero));
+    let group = load_group(&fixture);
+    assert_eq!(group.num_members, 3);
+    assert_eq!(group.roots, vec![hex_input(root), hex_input(hash(hash(fr(1), fr(2)), hash(zero, zero)))]);
+
+    let signal_msg = |root: ark_bn254::Fr, signal: u64, nullifier_hash: ark_bn254::Fr, proof: &str| ExecuteMsg::Signal {
+        group_id,
+        merkle_tree_root: hex_input(root),
+        signal: Uint256::from(signal),
+        nullifier_hash: hex_input(nullifier_hash),
+        external_nullifier: Uint256::from(7u64),
+        proof: proof.to_string(),
+    };
+    let external_nullifier_hash = semaphore::hash_to_field(Uint256::from(7u64));
+    let nullifier_hash = fr(5) * external_nullifier_hash;
+    let circuit = SemaphoreStandIn {
+        identity_nullifier: Some(fr(5)),
+        public_signals: Some([root, nullifier_hash, semaphore::hash_to_field(Uint256::from(1u64)), external_nullifier_hash]),
+    };
+    let proof = bn254_proof_str(&Groth16::<Bn254>::prove(&pk, circuit, &mut OsRng).unwrap());
+
+    // The empty tree's root has dropped out of the two root history
+    let err = fixture.execute("voter", signal_msg(empty_root, 1, nullifier_hash, &proof)).unwrap_err();
+    assert!(matches!(err, ContractError::MerkleRootNotAccepted { .. }));
+    let err = fixture.execute("voter", signal_msg(root, 2, nullifier_hash, &proof)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidProof {}));
+    let res = fixture.execute("voter", signal_msg(root, 1, nullifier_hash, &proof)).unwrap();
+    assert_eq!(res.attributes[0].value, "signal");
+    let err = fixture.execute("voter", signal_msg(root, 1, nullifier_hash, &proof)).unwrap_err();
+    assert!(matches!(err, ContractError::NullifierAlreadyUsed { .. }));
+
+    // A depth 2 tree holds four members
+    let _ = fixture.execute("dao", add_msg(4)).unwrap();
+    let err = fixture.execute("dao", add_msg(5)).unwrap_err();
+    assert!(matches!(err, ContractError::GroupFull { .. }));
+}
@@ -0,0 +1,351 @@
+use sha2::{Digest, Sha256};
+use super::*;
+use crate::msg::{CircuitResponse, CircuitsResponse, KeyVersionsResponse, VerifyProofBatchResponse};
+use crate::state::{Deadline, GracePeriod, KeyStatus, ProofSystem};
+
+const WEEK: u64 = 7 * 24 * 60 * 60;
+
+fn list_versions_msg(circuit_id: &str) -> QueryMsg {
+    QueryMsg::ListKeyVersions { circuit_id: circuit_id.to_string(), start_after: None, limit: None }
+}
+
+// VerifyProof of the multiply circuit's c = 12 at env, against key_version if given
+fn verify_at(fixture: &Fixture, env: &Env, proof: &str, key_version: Option<u64>) -> Result<VerifyProofResponse, ContractError> {
+    let query_msg = QueryMsg::VerifyProof {
+        circuit_id: "multiply".to_string(),
+        proof: proof.to_string(),
+        public_inputs: vec![decimal(12)],
+        key_version,
+        expected_vkey_hash: None,
+        named_inputs: None,
+    };
+    fixture.query_at(env, query_msg)
+}
+
+#[test]
+fn test_register_verifying_key() {
+    let mut fixture = Fixture::new();
+    let vkey: VkeyStr = serde_json::from_str(DEMO_VKEY).unwrap();
+
+    // Only the admin can register keys
+    let err = fixture.execute("addr2", register_msg("multiply", vkey.clone())).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    let response = fixture.register("multiply", vkey);
+    assert_eq!(response.attributes[1].value, "multiply");
+}
+
+#[test]
+fn test_register_invalid_verifying_key() {
+    let mut fixture = Fixture::new();
+
+    // Point not on the curve
+    let mut vkey: VkeyStr = serde_json::from_str(DEMO_VKEY).unwrap();
+    edit_bytes(&mut vkey.alpha_1, |bytes| bytes[95] ^= 1);
+    let err = fixture.execute(ADMIN, register_msg("multiply", vkey)).unwrap_err();
+    assert!(matches!(err, ContractError::PointNotOnCurve { field } if field == "alpha_1"));
+
+    // Truncated point
+    let mut vkey: VkeyStr = serde_json::from_str(DEMO_VKEY).unwrap();
+    edit_bytes(&mut vkey.ic[1], |bytes| {
+        bytes.pop();
+    });
+    let err = fixture.execute(ADMIN, register_msg("multiply", vkey)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidPointLength { field, expected: 96, actual: 95 } if field == "ic[1]"));
+
+    // No ic points at all
+    let mut vkey: VkeyStr = serde_json::from_str(DEMO_VKEY).unwrap();
+    vkey.ic.clear();
+    let err = fixture.execute(ADMIN, register_msg("multiply", vkey)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidVerifyingKey {}));
+}
+
+#[test]
+fn test_key_lifecycle() {
+    let mut fixture = Fixture::new();
+    let env = fixture.env.clone();
+
+    // Two trusted setups for the same circuit
+    let old_params = setup_multiply_demo(1);
+    let new_params = setup_multiply_demo(1);
+    fixture.register("multiply", to_vkey_str(&old_params));
+    fixture.register("multiply", to_vkey_str(&new_params));
+    let old_proof = prove_multiply_demo(&old_params, &[(Scalar::from(3), Scalar::from(4))]);
+    let new_proof = prove_multiply_demo(&new_params, &[(Scalar::from(3), Scalar::from(4))]);
+    let list_versions = |fixture: &Fixture, env: &Env| -> KeyVersionsResponse {
+        fixture.query_at(env, list_versions_msg("multiply")).unwrap()
+    };
+
+    // Proofs made before the rotation still verify, against the deprecated key
+    let result = verify_at(&fixture, &env, &new_proof, None).unwrap();
+    assert!(result.valid);
+    assert_eq!(result.key_version, 2);
+    let result = verify_at(&fixture, &env, &old_proof, None).unwrap();
+    assert!(result.valid);
+    assert_eq!(result.key_version, 1);
+    let result = verify_at(&fixture, &env, &old_proof, Some(2)).unwrap();
+    assert_eq!(result.failure, Some(VerificationFailure::PairingFailure));
+
+    let versions = list_versions(&fixture, &env);
+    assert_eq!(versions.active_version, Some(2));
+    let statuses: Vec<(u64, KeyStatus, bool)> =
+        versions.versions.iter().map(|info| (info.version, info.status.clone(), info.usable)).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            (1, KeyStatus::Deprecated { until: Some(Deadline::Time(env.block.time.plus_seconds(WEEK))) }, true),
+            (2, KeyStatus::Active, true),
+        ]
+    );
+
+    // Only the admin manages keys
+    let deprecate_msg = ExecuteMsg::DeprecateVerifyingKey {
+        circuit_id: "multiply".to_string(),
+        version: 1,
+        grace_period: Some(GracePeriod::Blocks(10)),
+    };
+    let err = fixture.execute("addr2", deprecate_msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let _ = fixture.execute(ADMIN, deprecate_msg).unwrap();
+
+    // The old key is accepted until the grace period runs out
+    let mut later = env.clone();
+    later.block.height += 9;
+    assert!(verify_at(&fixture, &later, &old_proof, None).unwrap().valid);
+    later.block.height += 1;
+    let result = verify_at(&fixture, &later, &old_proof, None).unwrap();
+    assert_eq!(result.key_version, 2);
+    assert_eq!(result.failure, Some(VerificationFailure::PairingFailure));
+    let err = verify_at(&fixture, &later, &old_proof, Some(1)).unwrap_err();
+    assert!(matches!(err, ContractError::KeyVersionNotUsable { version: 1 }));
+    assert!(!list_versions(&fixture, &later).versions[0].usable);
+
+    // Revoking the active key leaves the circuit without one
+    let revoke_msg = ExecuteMsg::RevokeVerifyingKey { circuit_id: "multiply".to_string(), version: 2 };
+    let _ = fixture.execute(ADMIN, revoke_msg).unwrap();
+    assert_eq!(list_versions(&fixture, &env).active_version, None);
+    let result = verify_at(&fixture, &env, &new_proof, None).unwrap();
+    assert_eq!(result.key_version, 1);
+    assert!(!result.valid);
+    let err = verify_at(&fixture, &later, &new_proof, None).unwrap_err();
+    assert!(matches!(err, ContractError::NoUsableKey { .. }));
+
+    // Revoked keys cannot come back, deprecated ones can
+    let activate_msg = ExecuteMsg::ActivateVerifyingKey { circuit_id: "multiply".to_string(), version: 2 };
+    let err = fixture.execute(ADMIN, activate_msg).unwrap_err();
+    assert!(matches!(err, ContractError::KeyRevoked { version: 2 }));
+    let activate_msg = ExecuteMsg::ActivateVerifyingKey { circuit_id: "multiply".to_string(), version: 1 };
+    let _ = fixture.execute(ADMIN, activate_msg).unwrap();
+    assert!(verify_at(&fixture, &later, &old_proof, None).unwrap().valid);
+
+    let revoke_msg = ExecuteMsg::RevokeVerifyingKey { circuit_id: "multiply".to_string(), version: 3 };
+    let err = fixture.execute(ADMIN, revoke_msg).unwrap_err();
+    assert!(matches!(err, ContractError::UnknownKeyVersion { version: 3, .. }));
+}
+
+#[test]
+fn test_key_rotation_bounds() {
+    let mut fixture = Fixture::new();
+    let env = fixture.env.clone();
+
+    // The key replaced by version 2 gets 100 blocks, the one replaced by version 3 the default
+    let params: Vec<Parameters<Bls12>> = (0..3).map(|_| setup_multiply_demo(1)).collect();
+    for (params, grace_period) in params.iter().zip([None, Some(GracePeriod::Blocks(100)), None]) {
+        let register_msg = ExecuteMsg::RegisterVerifyingKey {
+            circuit_id: "multiply".to_string(),
+            vkey: to_vkey_str(params),
+            nullifier: None,
+            grace_period,
+        };
+        let _ = fixture.execute(ADMIN, register_msg).unwrap();
+    }
+    let proofs: Vec<String> =
+        params.iter().map(|params| prove_multiply_demo(params, &[(Scalar::from(3), Scalar::from(4))])).collect();
+
+    let versions: KeyVersionsResponse = fixture.query(list_versions_msg("multiply")).unwrap();
+    let statuses: Vec<KeyStatus> = versions.versions.into_iter().map(|info| info.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            KeyStatus::Deprecated { until: Some(Deadline::Height(env.block.height + 100)) },
+            KeyStatus::Deprecated { until: Some(Deadline::Time(env.block.time.plus_seconds(WEEK))) },
+            KeyStatus::Active,
+        ]
+    );
+
+    // Only the active key and the latest deprecated one are tried, older keys have to be asked for
+    let result = verify_at(&fixture, &env, &proofs[1], None).unwrap();
+    assert!(result.valid);
+    assert_eq!(result.key_version, 2);
+    let result = verify_at(&fixture, &env, &proofs[0], None).unwrap();
+    assert_eq!((result.key_version, result.failure), (3, Some(VerificationFailure::PairingFailure)));
+    assert!(verify_at(&fixture, &env, &proofs[0], Some(1)).unwrap().valid);
+
+    // Replaced keys stop verifying once their grace period is over
+    let mut later = env.clone();
+    later.block.height += 100;
+    let err = verify_at(&fixture, &later, &proofs[0], Some(1)).unwrap_err();
+    assert!(matches!(err, ContractError::KeyVersionNotUsable { version: 1 }));
+    later.block.time = later.block.time.plus_seconds(WEEK);
+    let result = verify_at(&fixture, &later, &proofs[1], None).unwrap();
+    assert_eq!((result.key_version, result.failure), (3, Some(VerificationFailure::PairingFailure)));
+
+    // Grace periods past the largest height or time are refused rather than wrapped into the past
+    for grace_period in [GracePeriod::Blocks(u64::MAX), GracePeriod::Seconds(u64::MAX / 1_000_000_000)] {
+        let deprecate_msg = ExecuteMsg::DeprecateVerifyingKey {
+            circuit_id: "multiply".to_string(),
+            version: 2,
+            grace_period: Some(grace_period),
+        };
+        let err = fixture.execute(ADMIN, deprecate_msg).unwrap_err();
+        assert!(matches!(err, ContractError::GracePeriodTooLong {}));
+    }
+    let register_msg = ExecuteMsg::RegisterVerifyingKey {
+        circuit_id: "multiply".to_string(),
+        vkey: to_vkey_str(&params[0]),
+        nullifier: None,
+        grace_period: Some(GracePeriod::Seconds(u64::MAX)),
+    };
+    let err = fixture.execute(ADMIN, register_msg).unwrap_err();
+    assert!(matches!(err, ContractError::GracePeriodTooLong {}));
+}
+
+#[test]
+fn test_batch_after_rotation() {
+    let mut fixture = Fixture::new();
+    let env = fixture.env.clone();
+    let old_params = setup_multiply_demo(1);
+    let new_params = setup_multiply_demo(1);
+    fixture.register("multiply", to_vkey_str(&old_params));
+    let old_proof = prove_multiply_demo(&old_params, &[(Scalar::from(3), Scalar::from(4))]);
+    fixture.register("multiply", to_vkey_str(&new_params));
+    let new_proof = prove_multiply_demo(&new_params, &[(Scalar::from(3), Scalar::from(4))]);
+    let batch_msg = QueryMsg::VerifyProofBatch {
+        circuit_id: "multiply".to_string(),
+        proofs: vec![
+            (new_proof.clone(), vec![decimal(12)]),
+            (old_proof.clone(), vec![decimal(12)]),
+            (old_proof, vec![decimal(13)]),
+        ],
+        key_version: None,
+    };
+
+    // Proofs made before the rotation pass through the deprecated key
+    let response: VerifyProofBatchResponse = fixture.query(batch_msg.clone()).unwrap();
+    assert_eq!(response, VerifyProofBatchResponse { all_valid: false, results: vec![true, true, false] });
+
+    let mut later = env;
+    later.block.time = later.block.time.plus_seconds(WEEK);
+    let response: VerifyProofBatchResponse = fixture.query_at(&later, batch_msg).unwrap();
+    assert_eq!(response.results, vec![true, false, false]);
+
+    // A requested version is the only one used
+    let batch_msg = QueryMsg::VerifyProofBatch {
+        circuit_id: "multiply".to_string(),
+        proofs: vec![(new_proof, vec![decimal(12)])],
+        key_version: Some(1),
+    };
+    let response: VerifyProofBatchResponse = fixture.query(batch_msg).unwrap();
+    assert_eq!(response.results, vec![false]);
+}
+
+#[test]
+fn test_circuit_queries() {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(2);
+    let mut preimage = b"bls12_381".to_vec();
+    preimage.extend_from_slice(&params.vk.alpha_g1.to_uncompressed());
+    preimage.extend_from_slice(&params.vk.beta_g2.to_uncompressed());
+    preimage.extend_from_slice(&params.vk.gamma_g2.to_uncompressed());
+    preimage.extend_from_slice(&params.vk.delta_g2.to_uncompressed());
+    for point in params.vk.ic.iter() {
+        preimage.extend_from_slice(&point.to_uncompressed());
+    }
+    let key_hash = hex::encode(Sha256::digest(&preimage));
+
+    // The hash does not depend on the point encoding
+    for (circuit_id, vkey) in [("c", to_vkey_str(&params)), ("a", to_compressed_vkey_str(&params)), ("b", to_vkey_str(&params))] {
+        fixture.register(circuit_id, vkey);
+        fixture.env.block.height += 1;
+    }
+
+    let circuit: CircuitResponse = fixture.query(QueryMsg::Circuit { id: "a".to_string() }).unwrap();
+    assert_eq!(
+        circuit,
+        CircuitResponse {
+            id: "a".to_string(),
+            active_version: Some(1),
+            proof_system: ProofSystem::Groth16,
+            key_version: 1,
+            status: KeyStatus::Active,
+            num_public_inputs: 2,
+            curve: Curve::Bls12_381,
+            key_hash: key_hash.clone(),
+            registered_height: mock_env().block.height + 1,
+            registrant: fixture.addr(ADMIN),
+            input_names: vec![],
+            template: None,
+        }
+    );
+
+    let page: CircuitsResponse = fixture.query(QueryMsg::ListCircuits { start_after: None, limit: Some(2) }).unwrap();
+    let ids: Vec<&str> = page.circuits.iter().map(|circuit| circuit.id.as_str()).collect();
+    assert_eq!(ids, vec!["a", "b"]);
+    assert!(page.circuits.iter().all(|circuit| circuit.key_hash == key_hash));
+
+    let page: CircuitsResponse =
+        fixture.query(QueryMsg::ListCircuits { start_after: Some("b".to_string()), limit: None }).unwrap();
+    assert_eq!(page.circuits.len(), 1);
+    assert_eq!(page.circuits[0].id, "c");
+
+    // Without an active key the latest one is described
+    let revoke_msg = ExecuteMsg::RevokeVerifyingKey { circuit_id: "c".to_string(), version: 1 };
+    let _ = fixture.execute(ADMIN, revoke_msg).unwrap();
+    let circuit: CircuitResponse = fixture.query(QueryMsg::Circuit { id: "c".to_string() }).unwrap();
+    assert_eq!(circuit.active_version, None);
+    assert_eq!(circuit.status, KeyStatus::Revoked);
+
+    assert!(fixture.query::<CircuitResponse>(QueryMsg::Circuit { id: "unknown".to_string() }).is_err());
+}
+
+#[test]
+fn test_expected_vkey_hash() {
+    let mut fixture = Fixture::new();
+    let old_params = setup_multiply_demo(1);
+    let new_params = setup_multiply_demo(1);
+    let mut key_hashes = Vec::new();
+    for params in [&old_params, &new_params] {
+        let response = fixture.register("multiply", to_vkey_str(params));
+        key_hashes.push(response.attributes[3].value.clone());
+    }
+    assert_ne!(key_hashes[0], key_hashes[1]);
+
+    let versions: KeyVersionsResponse = fixture.query(list_versions_msg("multiply")).unwrap();
+    let listed: Vec<String> = versions.versions.into_iter().map(|info| info.key_hash).collect();
+    assert_eq!(listed, key_hashes);
+
+    let old_proof = prove_multiply_demo(&old_params, &[(Scalar::from(3), Scalar::from(4))]);
+    let verify = |expected_vkey_hash: &str| {
+        fixture.query::<VerifyProofResponse>(QueryMsg::VerifyProof {
+            circuit_id: "multiply".to_string(),
+            proof: old_proof.clone(),
+            public_inputs: vec![decimal(12)],
+            key_version: None,
+            expected_vkey_hash: Some(expected_vkey_hash.to_string()),
+            named_inputs: None,
+        })
+    };
+
+    // Pinned to the old ceremony, in any case and with or without 0x
+    let result = verify(&format!("0x{}", key_hashes[0].to_uppercase())).unwrap();
+    assert!(result.valid);
+    assert_eq!(result.key_version, 1);
+
+    // Pinned to the new ceremony, the deprecated key is not tried
+    let result = verify(&key_hashes[1]).unwrap();
+    assert_eq!(result.key_version, 2);
+    assert_eq!(result.failure, Some(VerificationFailure::PairingFailure));
+
+    let err = verify(&"00".repeat(32)).unwrap_err();
+    assert!(matches!(err, ContractError::VkeyHashMismatch { actual, .. } if actual == key_hashes[1]));
+}
@@ -0,0 +1,56 @@
+use cosmwasm_std::Storage;
+use contract_migration::MigrationError;
+use super::*;
+use crate::contract::{migrate, CONTRACT_NAME, CONTRACT_VERSION};
+use crate::msg::{CircuitResponse, ConfigResponse, MigrateMsg};
+
+#[test]
+fn test_migrate_legacy_layout() {
+    // The shared 0.1.0 name over state 0.1.x of this contract cannot have left, here that of the
+    // cross_chain verifier, which stored no config
+    let mut deps = mock_dependencies();
+    cw2::set_contract_version(&mut deps.storage, "crates.io:verifier-contract", "0.1.0").unwrap();
+    let migrate_msg = MigrateMsg {
+        legacy_contract: Some("crates.io:verifier-contract".to_string()),
+        legacy_circuit_id: None,
+    };
+    let err = migrate(deps.as_mut(), mock_env(), migrate_msg).unwrap_err();
+    assert!(matches!(err, ContractError::Migration(MigrationError::LegacyLayoutMismatch { .. })));
+}
+
+#[test]
+fn test_migrate_from_hardcoded_key() {
+    // State as 0.1.0 left it: a config with a plain admin address and nothing else
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    cw2::set_contract_version(&mut deps.storage, "crates.io:verifier-contract", "0.1.0").unwrap();
+    let config = format!(r#"{{"admin_address":"{}"}}"#, deps.api.addr_make(ADMIN));
+    deps.storage.set(b"state", config.as_bytes());
+
+    // Other contracts stored the same name, so it has to be confirmed
+    let migrate_msg = MigrateMsg { legacy_contract: None, legacy_circuit_id: Some("multiply".to_string()) };
+    let err = migrate(deps.as_mut(), env.clone(), migrate_msg).unwrap_err();
+    assert!(matches!(err, ContractError::Migration(MigrationError::UnconfirmedLegacyContract { .. })));
+
+    let migrate_msg = MigrateMsg {
+        legacy_contract: Some("crates.io:verifier-contract".to_string()),
+        legacy_circuit_id: Some("multiply".to_string()),
+    };
+    let response = migrate(deps.as_mut(), env.clone(), migrate_msg).unwrap();
+    assert_eq!(response.attributes[1].value, "0.1.0");
+    let version = cw2::get_contract_version(&deps.storage).unwrap();
+    assert_eq!((version.contract.as_str(), version.version.as_str()), (CONTRACT_NAME, CONTRACT_VERSION));
+
+    // From here on the migrated contract behaves like an instantiated one
+    let mut fixture = Fixture { deps, env };
+    let config: ConfigResponse = fixture.query(QueryMsg::Config {}).unwrap();
+    assert_eq!(config.admin_address, Some(fixture.addr(ADMIN)));
+
+    // The key that used to be hardcoded is now the first version of the circuit
+    let circuit: CircuitResponse = fixture.query(QueryMsg::Circuit { id: "multiply".to_string() }).unwrap();
+    assert_eq!(circuit.active_version, Some(1));
+    assert_eq!(circuit.num_public_inputs, 1);
+    assert_eq!(circuit.registrant, fixture.env.contract.address);
+    let response = fixture.register("other", serde_json::from_str(DEMO_VKEY).unwrap());
+    assert_eq!(response.attributes[3].value, circuit.key_hash);
+}
@@ -0,0 +1,231 @@
+use cosmwasm_std::{
+    testing::{message_info, mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage}, from_json, Addr, Binary, Env,
+    OwnedDeps, Response,
+};
+use bellman::{Circuit, ConstraintSystem, SynthesisError};
+use bellman::groth16::{create_random_proof, generate_random_parameters, Parameters};
+use bls12_381::{Bls12, Scalar};
+use ark_bn254::Bn254;
+use ark_ff::{BigInteger, PrimeField as ArkPrimeField};
+use rand_core::OsRng;
+use serde::de::DeserializeOwned;
+use crate::ContractError;
+use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, VerificationFailure, VerifyProofResponse};
+use crate::state::{Curve, PointEncoding, ProofStr, PublicInput, VkeyStr};
+use super::{execute, instantiate, query};
+
+mod admin;
+mod credentials;
+mod groups;
+mod keys;
+mod migrate;
+mod nullifiers;
+mod plonk;
+mod proptests;
+mod snarkjs;
+mod verify;
+
+// Verifying key of the original c <- a * b demo circuit
+const DEMO_VKEY: &str = include_str!("../../legacy_vkey.json");
+
+const ADMIN: &str = "addr1";
+
+// A contract instantiated with ADMIN as its admin. Messages go through the entry points, queries
+// are answered at env unless another block is given. Senders are names, see addr
+struct Fixture {
+    deps: OwnedDeps<MockStorage, MockApi, MockQuerier>,
+    env: Env,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let admin = deps.api.addr_make(ADMIN);
+        let msg = InstantiateMsg {
+            admin_address: admin.to_string(),
+        };
+        let _ = instantiate(deps.as_mut(), env.clone(), message_info(&admin, &[]), msg).unwrap();
+        Fixture { deps, env }
+    }
+
+    // The address MockApi accepts for a name such as ADMIN
+    fn addr(&self, name: &str) -> Addr {
+        self.deps.api.addr_make(name)
+    }
+
+    fn execute(&mut self, sender: &str, msg: ExecuteMsg) -> Result<Response, ContractError> {
+        let info = message_info(&self.addr(sender), &[]);
+        execute(self.deps.as_mut(), self.env.clone(), info, msg)
+    }
+
+    // Registers vkey as the admin, without a nullifier source and with the default grace period
+    fn register(&mut self, circuit_id: &str, vkey: VkeyStr) -> Response {
+        self.execute(ADMIN, register_msg(circuit_id, vkey)).unwrap()
+    }
+
+    fn query<T: DeserializeOwned>(&self, msg: QueryMsg) -> Result<T, ContractError> {
+        self.query_at(&self.env, msg)
+    }
+
+    fn query_at<T: DeserializeOwned>(&self, env: &Env, msg: QueryMsg) -> Result<T, ContractError> {
+        Ok(from_json(query(self.deps.as_ref(), env.clone(), msg)?)?)
+    }
+
+    // VerifyProof against whichever key versions the contract picks
+    fn verify(&self, circuit_id: &str, proof: &str, public_inputs: Vec<PublicInput>) -> VerifyProofResponse {
+        self.query(verify_msg(circuit_id, proof, public_inputs)).unwrap()
+    }
+}
+
+fn register_msg(circuit_id: &str, vkey: VkeyStr) -> ExecuteMsg {
+    ExecuteMsg::RegisterVerifyingKey {
+        circuit_id: circuit_id.to_string(),
+        vkey,
+        nullifier: None,
+        grace_period: None,
+    }
+}
+
+fn verify_msg(circuit_id: &str, proof: &str, public_inputs: Vec<PublicInput>) -> QueryMsg {
+    QueryMsg::VerifyProof {
+        circuit_id: circuit_id.to_string(),
+        proof: proof.to_string(),
+        public_inputs,
+        key_version: None,
+        expected_vkey_hash: None,
+        named_inputs: None,
+    }
+}
+
+// c_i <- a_i * b_i for every pair, with each c_i as a public input
+struct MultiplyDemo {
+    pairs: Vec<(Option<Scalar>, Option<Scalar>)>,
+}
+
+impl Circuit<Scalar> for MultiplyDemo {
+    fn synthesize<CS: ConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        for (i, (a_val, b_val)) in self.pairs.into_iter().enumerate() {
+            let a = cs.alloc(|| format!("a_{}", i), || a_val.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.alloc(|| format!("b_{}", i), || b_val.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = cs.alloc_input(
+                || format!("c_{}", i),
+                || Ok(a_val.ok_or(SynthesisError::AssignmentMissing)? * b_val.ok_or(SynthesisError::AssignmentMissing)?),
+            )?;
+            cs.enforce(|| format!("a_{} * b_{} = c_{}", i, i, i), |lc| lc + a, |lc| lc + b, |lc| lc + c);
+        }
+        Ok(())
+    }
+}
+
+fn setup_multiply_demo(num_inputs: usize) -> Parameters<Bls12> {
+    let circuit = MultiplyDemo { pairs: vec![(None, None); num_inputs] };
+    generate_random_parameters::<Bls12, _, _>(circuit, &mut OsRng).unwrap()
+}
+
+fn decimal(value: u64) -> PublicInput {
+    PublicInput::Decimal(value.to_string())
+}
+
+fn to_vkey_str(params: &Parameters<Bls12>) -> VkeyStr {
+    VkeyStr {
+        curve: Curve::Bls12_381,
+        encoding: PointEncoding::Uncompressed,
+        alpha_1: Binary::from(params.vk.alpha_g1.to_uncompressed().as_ref()),
+        beta_2: Binary::from(params.vk.beta_g2.to_uncompressed().as_ref()),
+        gamma_2: Binary::from(params.vk.gamma_g2.to_uncompressed().as_ref()),
+        delta_2: Binary::from(params.vk.delta_g2.to_uncompressed().as_ref()),
+        ic: params.vk.ic.iter().map(|point| Binary::from(point.to_uncompressed().as_ref())).collect(),
+    }
+}
+
+fn to_compressed_vkey_str(params: &Parameters<Bls12>) -> VkeyStr {
+    VkeyStr {
+        curve: Curve::Bls12_381,
+        encoding: PointEncoding::Compressed,
+        alpha_1: Binary::from(params.vk.alpha_g1.to_compressed().as_ref()),
+        beta_2: Binary::from(params.vk.beta_g2.to_compressed().as_ref()),
+        gamma_2: Binary::from(params.vk.gamma_g2.to_compressed().as_ref()),
+        delta_2: Binary::from(params.vk.delta_g2.to_compressed().as_ref()),
+        ic: params.vk.ic.iter().map(|point| Binary::from(point.to_compressed().as_ref())).collect(),
+    }
+}
+
+fn parse_error(err: ContractError) -> Option<VerificationFailure> {
+    Some(VerificationFailure::ParseError { msg: err.to_string() })
+}
+
+fn prove_multiply_demo(params: &Parameters<Bls12>, pairs: &[(Scalar, Scalar)]) -> String {
+    prove_multiply_demo_with_encoding(params, pairs, PointEncoding::Uncompressed)
+}
+
+fn prove_multiply_demo_with_encoding(
+    params: &Parameters<Bls12>,
+    pairs: &[(Scalar, Scalar)],
+    encoding: PointEncoding,
+) -> String {
+    let circuit = MultiplyDemo {
+        pairs: pairs.iter().map(|(a, b)| (Some(*a), Some(*b))).collect(),
+    };
+    let proof = create_random_proof(circuit, params, &mut OsRng).unwrap();
+    let proof_str = match encoding {
+        PointEncoding::Uncompressed => ProofStr {
+            encoding,
+            pi_a: Binary::from(proof.a.to_uncompressed().as_ref()),
+            pi_b: Binary::from(proof.b.to_uncompressed().as_ref()),
+            pi_c: Binary::from(proof.c.to_uncompressed().as_ref()),
+        },
+        PointEncoding::Compressed => ProofStr {
+            encoding,
+            pi_a: Binary::from(proof.a.to_compressed().as_ref()),
+            pi_b: Binary::from(proof.b.to_compressed().as_ref()),
+            pi_c: Binary::from(proof.c.to_compressed().as_ref()),
+        },
+    };
+    serde_json::to_string(&proof_str).unwrap()
+}
+
+// Binary keeps its bytes private, so points are edited as a Vec and stored back
+fn edit_bytes(binary: &mut Binary, edit: impl FnOnce(&mut Vec<u8>)) {
+    let mut bytes = binary.to_vec();
+    edit(&mut bytes);
+    *binary = Binary::new(bytes);
+}
+
+fn bn254_g1_bytes(point: &ark_bn254::G1Affine) -> Binary {
+    Binary::new([point.x.into_bigint().to_bytes_be(), point.y.into_bigint().to_bytes_be()].concat())
+}
+
+fn bn254_g2_bytes(point: &ark_bn254::G2Affine) -> Binary {
+    Binary::new(
+        [
+            point.x.c1.into_bigint().to_bytes_be(),
+            point.x.c0.into_bigint().to_bytes_be(),
+            point.y.c1.into_bigint().to_bytes_be(),
+            point.y.c0.into_bigint().to_bytes_be(),
+        ]
+        .concat(),
+    )
+}
+
+fn bn254_vkey_str(vk: &ark_groth16::VerifyingKey<Bn254>) -> VkeyStr {
+    VkeyStr {
+        curve: Curve::Bn254,
+        encoding: PointEncoding::Uncompressed,
+        alpha_1: bn254_g1_bytes(&vk.alpha_g1),
+        beta_2: bn254_g2_bytes(&vk.beta_g2),
+        gamma_2: bn254_g2_bytes(&vk.gamma_g2),
+        delta_2: bn254_g2_bytes(&vk.delta_g2),
+        ic: vk.gamma_abc_g1.iter().map(bn254_g1_bytes).collect(),
+    }
+}
+
+fn bn254_proof_str(proof: &ark_groth16::Proof<Bn254>) -> String {
+    serde_json::to_string(&ProofStr {
+        encoding: PointEncoding::Uncompressed,
+        pi_a: bn254_g1_bytes(&proof.a),
+        pi_b: bn254_g2_bytes(&proof.b),
+        pi_c: bn254_g1_bytes(&proof.c),
+    })
+    .unwrap()
+}
@@ -0,0 +1,183 @@
+use cosmwasm_std::{coins, BankMsg, CosmosMsg, SubMsg};
+use ff::PrimeField;
+use super::*;
+use crate::msg::MsgsHashResponse;
+use crate::state::{NullifierSource, msgs_hash};
+
+fn register_with_nullifier(circuit_id: &str, vkey: VkeyStr, nullifier: NullifierSource) -> ExecuteMsg {
+    ExecuteMsg::RegisterVerifyingKey {
+        circuit_id: circuit_id.to_string(),
+        vkey,
+        nullifier: Some(nullifier),
+        grace_period: None,
+    }
+}
+
+fn submit_msg(circuit_id: &str, proof: String, public_inputs: Vec<PublicInput>) -> ExecuteMsg {
+    ExecuteMsg::SubmitProof {
+        circuit_id: circuit_id.to_string(),
+        proof,
+        public_inputs,
+        key_version: None,
+    }
+}
+
+#[test]
+fn test_submit_proof() {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(2);
+    fixture.register("multiply", to_vkey_str(&params));
+
+    // Circuits registered without a nullifier source take no submissions
+    let pairs = [(Scalar::from(3), Scalar::from(4)), (Scalar::from(5), Scalar::from(6))];
+    let submit = submit_msg("multiply", prove_multiply_demo(&params, &pairs), vec![decimal(12), decimal(30)]);
+    let err = fixture.execute("anyone", submit.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NoNullifierSource { circuit_id } if circuit_id == "multiply"));
+
+    let _ = fixture.execute(ADMIN, register_with_nullifier("multiply", to_vkey_str(&params), NullifierSource::ProofHash)).unwrap();
+    let response = fixture.execute("anyone", submit.clone()).unwrap();
+    assert_eq!(response.attributes[0].value, "submit_proof");
+
+    // The same proof cannot be submitted twice
+    let err = fixture.execute("anyone", submit).unwrap_err();
+    assert!(matches!(err, ContractError::NullifierAlreadyUsed { nullifier } if nullifier == response.attributes[2].value));
+
+    // A fresh proof of the same statement hashes differently, which is why the proof hash does not
+    // stop replays
+    let submit = submit_msg("multiply", prove_multiply_demo(&params, &pairs), vec![decimal(12), decimal(30)]);
+    let _ = fixture.execute("anyone", submit).unwrap();
+
+    // Invalid proofs record nothing
+    let submit = submit_msg("multiply", prove_multiply_demo(&params, &pairs), vec![decimal(12), decimal(31)]);
+    let err = fixture.execute("anyone", submit).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidProof {}));
+
+    // With the second public input as nullifier, one proof per value of c_1 is accepted
+    let nullifier = NullifierSource::PublicInput { index: 1 };
+    let _ = fixture.execute(ADMIN, register_with_nullifier("claim", to_vkey_str(&params), nullifier)).unwrap();
+
+    let submit = submit_msg("claim", prove_multiply_demo(&params, &pairs), vec![decimal(12), decimal(30)]);
+    let response = fixture.execute("anyone", submit).unwrap();
+    assert_eq!(response.attributes[2].value, format!("1e{}", "0".repeat(62)));
+
+    let submit = submit_msg(
+        "claim",
+        prove_multiply_demo(&params, &[(Scalar::from(2), Scalar::from(7)), (Scalar::from(3), Scalar::from(10))]),
+        vec![decimal(14), PublicInput::HexBe(format!("{:0>64}", "1e"))],
+    );
+    let err = fixture.execute("anyone", submit).unwrap_err();
+    assert!(matches!(err, ContractError::NullifierAlreadyUsed { .. }));
+
+    // The nullifier has to be one of the circuit's public inputs
+    let nullifier = NullifierSource::PublicInput { index: 2 };
+    let err = fixture.execute(ADMIN, register_with_nullifier("claim", to_vkey_str(&params), nullifier)).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidNullifierIndex { index: 2 }));
+}
+
+#[test]
+fn test_nullifier_index_across_key_versions() {
+    let mut fixture = Fixture::new();
+    let old_params = setup_multiply_demo(2);
+    let new_params = setup_multiply_demo(3);
+    let register = register_with_nullifier("multiply", to_vkey_str(&old_params), NullifierSource::PublicInput { index: 1 });
+    let _ = fixture.execute(ADMIN, register).unwrap();
+
+    // Proofs for the 2 input key are still accepted, so the nullifier cannot move to a third input
+    let register = register_with_nullifier("multiply", to_vkey_str(&new_params), NullifierSource::PublicInput { index: 2 });
+    let err = fixture.execute(ADMIN, register).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidNullifierIndex { index: 2 }));
+    fixture.register("multiply", to_vkey_str(&new_params));
+
+    // A proof against the deprecated key is nullified by the input both keys have
+    let pairs = [(Scalar::from(3), Scalar::from(4)), (Scalar::from(5), Scalar::from(6))];
+    let submit = ExecuteMsg::SubmitProof {
+        circuit_id: "multiply".to_string(),
+        proof: prove_multiply_demo(&old_params, &pairs),
+        public_inputs: vec![decimal(12), decimal(30)],
+        key_version: Some(1),
+    };
+    let response = fixture.execute("anyone", submit.clone()).unwrap();
+    assert_eq!(response.attributes[2].value, format!("1e{}", "0".repeat(62)));
+    assert_eq!(response.attributes[3].value, "1");
+    let err = fixture.execute("anyone", submit).unwrap_err();
+    assert!(matches!(err, ContractError::NullifierAlreadyUsed { .. }));
+
+    // Once the 2 input key is revoked the third input can be used
+    let revoke_msg = ExecuteMsg::RevokeVerifyingKey { circuit_id: "multiply".to_string(), version: 1 };
+    let _ = fixture.execute(ADMIN, revoke_msg).unwrap();
+    let register = register_with_nullifier("multiply", to_vkey_str(&new_params), NullifierSource::PublicInput { index: 2 });
+    let _ = fixture.execute(ADMIN, register).unwrap();
+}
+
+#[test]
+fn test_verify_and_execute() {
+    let mut fixture = Fixture::new();
+
+    // c_0 commits to the messages, c_1 is the nullifier
+    let params = setup_multiply_demo(2);
+    let nullifier = NullifierSource::PublicInput { index: 1 };
+    let _ = fixture.execute(ADMIN, register_with_nullifier("claim", to_vkey_str(&params), nullifier)).unwrap();
+
+    let bank_msg: CosmosMsg = BankMsg::Send {
+        to_address: "claimer".to_string(),
+        amount: coins(100, "token"),
+    }
+    .into();
+    let hash_response: MsgsHashResponse = fixture.query(QueryMsg::MsgsHash { msgs: vec![bank_msg.clone()] }).unwrap();
+    let mut hash_bytes = msgs_hash(std::slice::from_ref(&bank_msg)).unwrap();
+    assert_eq!(hash_response.msgs_hash, PublicInput::HexBe(hex::encode(hash_bytes)));
+    hash_bytes.reverse();
+    let hash_scalar = Scalar::from_repr(hash_bytes).unwrap();
+    let proof = prove_multiply_demo(&params, &[(hash_scalar, Scalar::from(1)), (Scalar::from(7), Scalar::from(1))]);
+    let public_inputs = vec![hash_response.msgs_hash, decimal(7)];
+
+    let execute_msg = |proof: &str, public_inputs: Vec<PublicInput>, msgs: Vec<CosmosMsg>| ExecuteMsg::VerifyAndExecute {
+        circuit_id: "claim".to_string(),
+        proof: proof.to_string(),
+        public_inputs,
+        msgs,
+        key_version: None,
+    };
+    // Only circuits the admin set a messages hash input for
+    let claim = execute_msg(&proof, public_inputs.clone(), vec![bank_msg.clone()]);
+    let err = fixture.execute("claimer", claim.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::NoMsgsHashInput { circuit_id } if circuit_id == "claim"));
+
+    let set_msg = ExecuteMsg::SetMsgsHashInput { circuit_id: "claim".to_string(), index: 0 };
+    let err = fixture.execute("claimer", set_msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+    let bad_set_msg = ExecuteMsg::SetMsgsHashInput { circuit_id: "claim".to_string(), index: 2 };
+    let err = fixture.execute(ADMIN, bad_set_msg).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMsgsHashIndex { index: 2 }));
+    let _ = fixture.execute(ADMIN, set_msg).unwrap();
+
+    let response = fixture.execute("claimer", claim.clone()).unwrap();
+    assert_eq!(response.messages, vec![SubMsg::new(bank_msg.clone())]);
+    assert_eq!(response.attributes[2].value, format!("07{}", "0".repeat(62)));
+
+    // Someone else replaying the proof with a payment to themselves: the proof does not commit to
+    // their messages, and committing to them breaks the proof
+    let theft_msg: CosmosMsg = BankMsg::Send {
+        to_address: "attacker".to_string(),
+        amount: coins(100, "token"),
+    }
+    .into();
+    let err = fixture.execute("attacker", execute_msg(&proof, public_inputs.clone(), vec![theft_msg.clone()])).unwrap_err();
+    assert!(matches!(err, ContractError::MsgsHashMismatch { .. }));
+
+    let theft_hash = PublicInput::HexBe(hex::encode(msgs_hash(std::slice::from_ref(&theft_msg)).unwrap()));
+    let err = fixture.execute("attacker", execute_msg(&proof, vec![theft_hash, decimal(7)], vec![theft_msg])).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidProof {}));
+
+    // Nor can the original messages be sent twice
+    let err = fixture.execute("attacker", claim).unwrap_err();
+    assert!(matches!(err, ContractError::NullifierAlreadyUsed { .. }));
+
+    let err = fixture.execute("claimer", execute_msg("{}", public_inputs.clone(), vec![bank_msg.clone()])).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidProofJson { .. }));
+
+    // A re-randomized proof hashes differently, so the proof hash is no nullifier here
+    let _ = fixture.execute(ADMIN, register_with_nullifier("claim", to_vkey_str(&params), NullifierSource::ProofHash)).unwrap();
+    let err = fixture.execute("claimer", execute_msg(&proof, public_inputs, vec![bank_msg])).unwrap_err();
+    assert!(matches!(err, ContractError::ReplayableNullifier { circuit_id } if circuit_id == "claim"));
+}
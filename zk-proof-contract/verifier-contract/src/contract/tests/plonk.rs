@@ -0,0 +1,420 @@
+use super::*;
+use crate::msg::{CircuitResponse, VerifyProofBatchResponse};
+use crate::plonk::{PlonkProof, PlonkVkey};
+use crate::state::{NullifierSource, ProofSystem};
+
+// Minimal PLONK prover, enough to produce snarkjs style keys and proofs for the tests. The circuit
+// proves c_i = a_i * b_i with every c_i public: rows 0..k take the public inputs, rows k..2k the
+// products, and the remaining rows are empty. Nothing is blinded, so proofs are not zero knowledge.
+mod plonk_prover {
+    use ark_bn254::{Fr, G1Affine, G2Affine};
+    use ark_ec::{AffineRepr, CurveGroup};
+    use ark_ff::{Field, One, PrimeField, UniformRand, Zero};
+    use ark_poly::univariate::DensePolynomial;
+    use ark_poly::{DenseUVPolynomial, EvaluationDomain, Polynomial, Radix2EvaluationDomain};
+    use rand_core::OsRng;
+
+    use crate::plonk::{PlonkProof, PlonkVkey, Transcript};
+
+    const K1: u64 = 2;
+    const K2: u64 = 3;
+
+    pub struct Setup {
+        pub vkey: PlonkVkey,
+        num_inputs: usize,
+        domain: Radix2EvaluationDomain<Fr>,
+        tau: Fr,
+        // qm, ql, qr, qo, qc
+        selectors: Vec<DensePolynomial<Fr>>,
+        sigmas: Vec<DensePolynomial<Fr>>,
+        sigma_values: Vec<Vec<Fr>>,
+        commitments: Vec<G1Affine>,
+    }
+
+    pub fn setup(num_inputs: usize) -> Setup {
+        let n = (2 * num_inputs).next_power_of_two().max(4);
+        let domain = Radix2EvaluationDomain::<Fr>::new(n).unwrap();
+        let tau = Fr::rand(&mut OsRng);
+
+        let mut selector_values = vec![vec![Fr::zero(); n]; 5];
+        for i in 0..num_inputs {
+            selector_values[1][i] = Fr::one();
+            selector_values[0][num_inputs + i] = Fr::one();
+            selector_values[3][num_inputs + i] = -Fr::one();
+        }
+
+        // Wire a of public input row i is copied to wire c of gate row k + i
+        let shifts = [Fr::one(), Fr::from(K1), Fr::from(K2)];
+        let mut sigma_values: Vec<Vec<Fr>> =
+            shifts.iter().map(|shift| domain.elements().map(|w| *shift * w).collect()).collect();
+        for i in 0..num_inputs {
+            sigma_values[0][i] = shifts[2] * domain.element(num_inputs + i);
+            sigma_values[2][num_inputs + i] = domain.element(i);
+        }
+
+        let selectors: Vec<_> = selector_values.into_iter().map(|values| interpolate(&domain, values)).collect();
+        let sigmas: Vec<_> = sigma_values.iter().map(|values| interpolate(&domain, values.clone())).collect();
+        let commitments: Vec<G1Affine> = selectors.iter().chain(sigmas.iter()).map(|poly| commit(tau, poly)).collect();
+
+        let vkey = PlonkVkey {
+            protocol: "plonk".to_string(),
+            curve: "bn128".to_string(),
+            n_public: num_inputs,
+            power: domain.log_size_of_group,
+            k1: K1.to_string(),
+            k2: K2.to_string(),
+            qm: g1_json(&commitments[0]),
+            ql: g1_json(&commitments[1]),
+            qr: g1_json(&commitments[2]),
+            qo: g1_json(&commitments[3]),
+            qc: g1_json(&commitments[4]),
+            s1: g1_json(&commitments[5]),
+            s2: g1_json(&commitments[6]),
+            s3: g1_json(&commitments[7]),
+            x_2: g2_json(&(G2Affine::generator() * tau).into_affine()),
+            w: decimal(domain.group_gen),
+        };
+        Setup { vkey, num_inputs, domain, tau, selectors, sigmas, sigma_values, commitments }
+    }
+
+    // Returns the proof and its public inputs, the products
+    pub fn prove(setup: &Setup, pairs: &[(u64, u64)]) -> (PlonkProof, Vec<Fr>) {
+        let k = setup.num_inputs;
+        let domain = &setup.domain;
+        let n = domain.size();
+        let public: Vec<Fr> = pairs.iter().map(|(a, b)| Fr::from(*a) * Fr::from(*b)).collect();
+
+        let mut wires = vec![vec![Fr::zero(); n]; 3];
+        for (i, (a, b)) in pairs.iter().enumerate() {
+            wires[0][i] = public[i];
+            wires[0][k + i] = Fr::from(*a);
+            wires[1][k + i] = Fr::from(*b);
+            wires[2][k + i] = public[i];
+        }
+        let [a, b, c] = [0, 1, 2].map(|col| interpolate(domain, wires[col].clone()));
+        let (qm, ql, qr, qo, qc) =
+            (&setup.selectors[0], &setup.selectors[1], &setup.selectors[2], &setup.selectors[3], &setup.selectors[4]);
+        let (s1, s2, s3) = (&setup.sigmas[0], &setup.sigmas[1], &setup.sigmas[2]);
+        let commit = |poly: &DensePolynomial<Fr>| commit(setup.tau, poly);
+
+        let mut transcript = Transcript::default();
+        let (proof_a, proof_b, proof_c) = (commit(&a), commit(&b), commit(&c));
+        for point in setup.commitments.iter() {
+            transcript.add_point(point);
+        }
+        for input in public.iter() {
+            transcript.add_scalar(input);
+        }
+        for point in [&proof_a, &proof_b, &proof_c] {
+            transcript.add_point(point);
+        }
+        let beta = transcript.challenge();
+        transcript.add_scalar(&beta);
+        let gamma = transcript.challenge();
+
+        // Permutation accumulator
+        let shifts = [Fr::one(), Fr::from(K1), Fr::from(K2)];
+        let mut z_values = vec![Fr::one(); n];
+        for i in 0..n - 1 {
+            let mut num = Fr::one();
+            let mut den = Fr::one();
+            for col in 0..3 {
+                num *= wires[col][i] + beta * shifts[col] * domain.element(i) + gamma;
+                den *= wires[col][i] + beta * setup.sigma_values[col][i] + gamma;
+            }
+            z_values[i + 1] = z_values[i] * num * den.inverse().unwrap();
+        }
+        let z = interpolate(domain, z_values);
+        let proof_z = commit(&z);
+        transcript.add_scalar(&beta);
+        transcript.add_scalar(&gamma);
+        transcript.add_point(&proof_z);
+        let alpha = transcript.challenge();
+
+        // Quotient
+        let x = DensePolynomial::from_coefficients_vec(vec![Fr::zero(), Fr::one()]);
+        let z_omega = DensePolynomial::from_coefficients_vec(
+            z.coeffs.iter().zip(domain.elements()).map(|(coeff, w)| *coeff * w).collect(),
+        );
+        let mut pi_values = vec![Fr::zero(); n];
+        for (i, input) in public.iter().enumerate() {
+            pi_values[i] = -*input;
+        }
+        let pi = interpolate(domain, pi_values);
+        let mut l1_values = vec![Fr::zero(); n];
+        l1_values[0] = Fr::one();
+        let l1 = interpolate(domain, l1_values);
+
+        let gate = &(&(&(&(&(&(qm * &a) * &b) + &(ql * &a)) + &(qr * &b)) + &(qo * &c)) + qc) + &pi;
+        let wire_term = |wire: &DensePolynomial<Fr>, id: &DensePolynomial<Fr>| &(wire + &scale(id, beta)) + &constant(gamma);
+        let perm_id = &(&(&wire_term(&a, &x) * &wire_term(&b, &scale(&x, shifts[1]))) * &wire_term(&c, &scale(&x, shifts[2]))) * &z;
+        let perm_sigma = &(&(&wire_term(&a, s1) * &wire_term(&b, s2)) * &wire_term(&c, s3)) * &z_omega;
+        let boundary = &(&z - &constant(Fr::one())) * &l1;
+        let numerator = &(&gate + &scale(&(&perm_id - &perm_sigma), alpha)) + &scale(&boundary, alpha.square());
+        let (t, remainder) = numerator.divide_by_vanishing_poly(*domain).unwrap();
+        assert!(remainder.is_zero());
+        let mut t_coeffs = t.coeffs.clone();
+        assert!(t_coeffs.len() <= 3 * n);
+        t_coeffs.resize(3 * n, Fr::zero());
+        let [t1, t2, t3] = [0, 1, 2].map(|i| DensePolynomial::from_coefficients_slice(&t_coeffs[i * n..(i + 1) * n]));
+        let (proof_t1, proof_t2, proof_t3) = (commit(&t1), commit(&t2), commit(&t3));
+        transcript.add_scalar(&alpha);
+        for point in [&proof_t1, &proof_t2, &proof_t3] {
+            transcript.add_point(point);
+        }
+        let xi = transcript.challenge();
+
+        let xi_omega = xi * domain.group_gen;
+        let evals = [a.evaluate(&xi), b.evaluate(&xi), c.evaluate(&xi), s1.evaluate(&xi), s2.evaluate(&xi), z.evaluate(&xi_omega)];
+        let [eval_a, eval_b, eval_c, eval_s1, eval_s2, eval_zw] = evals;
+        transcript.add_scalar(&xi);
+        for eval in evals.iter() {
+            transcript.add_scalar(eval);
+        }
+        let v1 = transcript.challenge();
+
+        // Linearization, whose value at xi is -r0
+        let xin = xi.pow([n as u64]);
+        let zh = xin - Fr::one();
+        let l1_xi = l1.evaluate(&xi);
+        let e3a = eval_a + beta * eval_s1 + gamma;
+        let e3b = eval_b + beta * eval_s2 + gamma;
+        let r0 = pi.evaluate(&xi) - l1_xi * alpha.square() - e3a * e3b * (eval_c + gamma) * eval_zw * alpha;
+        let z_factor = (eval_a + beta * xi + gamma)
+            * (eval_b + beta * xi * shifts[1] + gamma)
+            * (eval_c + beta * xi * shifts[2] + gamma)
+            * alpha
+            + l1_xi * alpha.square();
+        let gates = &(&(&(&scale(qm, eval_a * eval_b) + &scale(ql, eval_a)) + &scale(qr, eval_b)) + &scale(qo, eval_c)) + qc;
+        let t_xi = &(&t1 + &scale(&t2, xin)) + &scale(&t3, xin.square());
+        let r = &(&(&gates + &scale(&z, z_factor)) - &scale(s3, alpha * beta * eval_zw * e3a * e3b)) - &scale(&t_xi, zh);
+        assert_eq!(r.evaluate(&xi), -r0);
+
+        let mut w = &r + &constant(r0);
+        let mut v = v1;
+        for (poly, eval) in [(&a, eval_a), (&b, eval_b), (&c, eval_c), (s1, eval_s1), (s2, eval_s2)] {
+            w = &w + &scale(&(poly - &constant(eval)), v);
+            v *= v1;
+        }
+        let proof_wxi = commit(&divide_by_linear(&w, xi));
+        let proof_wxiw = commit(&divide_by_linear(&(&z - &constant(eval_zw)), xi_omega));
+
+        let proof = PlonkProof {
+            protocol: "plonk".to_string(),
+            curve: "bn128".to_string(),
+            a: g1_json(&proof_a),
+            b: g1_json(&proof_b),
+            c: g1_json(&proof_c),
+            z: g1_json(&proof_z),
+            t1: g1_json(&proof_t1),
+            t2: g1_json(&proof_t2),
+            t3: g1_json(&proof_t3),
+            wxi: g1_json(&proof_wxi),
+            wxiw: g1_json(&proof_wxiw),
+            eval_a: decimal(eval_a),
+            eval_b: decimal(eval_b),
+            eval_c: decimal(eval_c),
+            eval_s1: decimal(eval_s1),
+            eval_s2: decimal(eval_s2),
+            eval_zw: decimal(eval_zw),
+        };
+        (proof, public)
+    }
+
+    fn interpolate(domain: &Radix2EvaluationDomain<Fr>, values: Vec<Fr>) -> DensePolynomial<Fr> {
+        DensePolynomial::from_coefficients_vec(domain.ifft(&values))
+    }
+
+    fn commit(tau: Fr, poly: &DensePolynomial<Fr>) -> G1Affine {
+        (G1Affine::generator() * poly.evaluate(&tau)).into_affine()
+    }
+
+    fn constant(value: Fr) -> DensePolynomial<Fr> {
+        DensePolynomial::from_coefficients_vec(vec![value])
+    }
+
+    fn scale(poly: &DensePolynomial<Fr>, factor: Fr) -> DensePolynomial<Fr> {
+        DensePolynomial::from_coefficients_vec(poly.coeffs.iter().map(|coeff| *coeff * factor).collect())
+    }
+
+    // Quotient of poly by (X - point), the remainder being zero by construction
+    fn divide_by_linear(poly: &DensePolynomial<Fr>, point: Fr) -> DensePolynomial<Fr> {
+        let mut quotient = vec![Fr::zero(); poly.coeffs.len().saturating_sub(1)];
+        let mut carry = Fr::zero();
+        for i in (1..poly.coeffs.len()).rev() {
+            carry = poly.coeffs[i] + carry * point;
+            quotient[i - 1] = carry;
+        }
+        DensePolynomial::from_coefficients_vec(quotient)
+    }
+
+    pub fn decimal<F: PrimeField>(value: F) -> String {
+        value.into_bigint().to_string()
+    }
+
+    fn g1_json(point: &G1Affine) -> Vec<String> {
+        match point.xy() {
+            Some((x, y)) => vec![decimal(*x), decimal(*y), "1".to_string()],
+            None => vec!["0".to_string(), "1".to_string(), "0".to_string()],
+        }
+    }
+
+    fn g2_json(point: &G2Affine) -> Vec<Vec<String>> {
+        let (x, y) = point.xy().unwrap();
+        vec![
+            vec![decimal(x.c0), decimal(x.c1)],
+            vec![decimal(y.c0), decimal(y.c1)],
+            vec!["1".to_string(), "0".to_string()],
+        ]
+    }
+}
+
+fn register_plonk_msg(circuit_id: &str, vkey: PlonkVkey, nullifier: Option<NullifierSource>) -> ExecuteMsg {
+    ExecuteMsg::RegisterPlonkVerifyingKey {
+        circuit_id: circuit_id.to_string(),
+        vkey,
+        nullifier,
+        grace_period: None,
+    }
+}
+
+#[test]
+fn test_verify_plonk_proof() {
+    let mut fixture = Fixture::new();
+    let setup = plonk_prover::setup(2);
+    let nullifier = Some(NullifierSource::PublicInput { index: 1 });
+    let response = fixture.execute(ADMIN, register_plonk_msg("plonk_multiply", setup.vkey.clone(), nullifier)).unwrap();
+    assert_eq!(response.attributes[0].value, "register_plonk_verifying_key");
+    assert_eq!(response.attributes[2].value, "1");
+
+    let (proof, public) = plonk_prover::prove(&setup, &[(3, 4), (5, 6)]);
+    assert_eq!(public, vec![ark_bn254::Fr::from(12u64), ark_bn254::Fr::from(30u64)]);
+    let verify = |fixture: &Fixture, proof: &PlonkProof, public_inputs: Vec<PublicInput>| {
+        fixture.verify("plonk_multiply", &serde_json::to_string(proof).unwrap(), public_inputs)
+    };
+
+    let response = verify(&fixture, &proof, vec![decimal(12), decimal(30)]);
+    assert!(response.valid);
+    assert_eq!(response.key_version, 1);
+    assert_eq!(response.failure, None);
+
+    let response = verify(&fixture, &proof, vec![decimal(12), decimal(31)]);
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+    let response = verify(&fixture, &proof, vec![decimal(12)]);
+    assert_eq!(response.failure, Some(VerificationFailure::InputMismatch { expected: 2, actual: 1 }));
+
+    let mut tampered = proof.clone();
+    tampered.eval_a = plonk_prover::decimal(ark_bn254::Fr::from(7u64));
+    assert_eq!(verify(&fixture, &tampered, vec![decimal(12), decimal(30)]).failure, Some(VerificationFailure::PairingFailure));
+    let mut tampered = proof.clone();
+    tampered.wxiw = tampered.wxi.clone();
+    assert_eq!(verify(&fixture, &tampered, vec![decimal(12), decimal(30)]).failure, Some(VerificationFailure::PairingFailure));
+    let mut tampered = proof.clone();
+    tampered.eval_zw = "9".repeat(78);
+    assert_eq!(
+        verify(&fixture, &tampered, vec![decimal(12), decimal(30)]).failure,
+        parse_error(ContractError::InvalidScalar { field: "eval_zw".to_string() })
+    );
+    let mut tampered = proof.clone();
+    tampered.protocol = "groth16".to_string();
+    assert_eq!(
+        verify(&fixture, &tampered, vec![decimal(12), decimal(30)]).failure,
+        parse_error(ContractError::UnsupportedProtocol { protocol: "groth16".to_string() })
+    );
+
+    // A circuit keeps the proof system of its first key
+    let err = fixture.execute(ADMIN, register_msg("plonk_multiply", to_vkey_str(&setup_multiply_demo(2)))).unwrap_err();
+    assert!(matches!(err, ContractError::ProofSystemMismatch { .. }));
+
+    let circuit: CircuitResponse = fixture.query(QueryMsg::Circuit { id: "plonk_multiply".to_string() }).unwrap();
+    assert_eq!(circuit.proof_system, ProofSystem::Plonk);
+    assert_eq!(circuit.curve, Curve::Bn254);
+    assert_eq!(circuit.num_public_inputs, 2);
+
+    let (other_proof, _) = plonk_prover::prove(&setup, &[(2, 2), (1, 9)]);
+    let batch_query_msg = QueryMsg::VerifyProofBatch {
+        circuit_id: "plonk_multiply".to_string(),
+        proofs: vec![
+            (serde_json::to_string(&proof).unwrap(), vec![decimal(12), decimal(30)]),
+            (serde_json::to_string(&other_proof).unwrap(), vec![decimal(4), decimal(10)]),
+            (serde_json::to_string(&other_proof).unwrap(), vec![decimal(4), decimal(9)]),
+            ("not a proof".to_string(), vec![decimal(4), decimal(9)]),
+        ],
+        key_version: None,
+    };
+    let get_result: VerifyProofBatchResponse = fixture.query(batch_query_msg).unwrap();
+    assert_eq!(get_result, VerifyProofBatchResponse { all_valid: false, results: vec![true, false, true, false] });
+
+    let submit_msg = ExecuteMsg::SubmitProof {
+        circuit_id: "plonk_multiply".to_string(),
+        proof: serde_json::to_string(&proof).unwrap(),
+        public_inputs: vec![decimal(12), decimal(30)],
+        key_version: None,
+    };
+    let _ = fixture.execute("addr2", submit_msg.clone()).unwrap();
+    let err = fixture.execute("addr2", submit_msg).unwrap_err();
+    assert!(matches!(err, ContractError::NullifierAlreadyUsed { .. }));
+}
+
+#[test]
+fn test_register_plonk_verifying_key() {
+    let mut fixture = Fixture::new();
+    let setup = plonk_prover::setup(1);
+    let mut register = |circuit_id: &str, vkey: PlonkVkey| fixture.execute(ADMIN, register_plonk_msg(circuit_id, vkey, None));
+
+    // w must generate exactly the 2^power roots of unity
+    let mut vkey = setup.vkey.clone();
+    vkey.w = "1".to_string();
+    assert!(matches!(register("plonk", vkey).unwrap_err(), ContractError::InvalidVerifyingKey {}));
+    let mut vkey = setup.vkey.clone();
+    vkey.power += 1;
+    assert!(matches!(register("plonk", vkey).unwrap_err(), ContractError::InvalidVerifyingKey {}));
+    let mut vkey = setup.vkey.clone();
+    vkey.qm[1] = "1".to_string();
+    assert!(matches!(register("plonk", vkey).unwrap_err(), ContractError::PointNotOnCurve { .. }));
+    let mut vkey = setup.vkey.clone();
+    vkey.curve = "bls12381".to_string();
+    assert!(matches!(register("plonk", vkey).unwrap_err(), ContractError::UnsupportedCurve { .. }));
+
+    let response = register("plonk", setup.vkey.clone()).unwrap();
+    let key_hash = response.attributes[3].value.clone();
+    // The hash only depends on the key, not on how its numbers were written
+    let mut vkey = setup.vkey.clone();
+    vkey.k1 = format!("000{}", vkey.k1);
+    let response = register("plonk_padded", vkey).unwrap();
+    assert_eq!(response.attributes[3].value, key_hash);
+
+    // PLONK keys cannot be added to a Groth16 circuit either
+    fixture.register("groth16", to_vkey_str(&setup_multiply_demo(1)));
+    let err = fixture.execute(ADMIN, register_plonk_msg("groth16", setup.vkey.clone(), None)).unwrap_err();
+    assert!(matches!(err, ContractError::ProofSystemMismatch { .. }));
+
+    let err = fixture.execute("addr2", register_plonk_msg("plonk", setup.vkey, None)).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+}
+
+// The tests above only check plonk.rs against plonk_prover, which shares its transcript. This one
+// checks it against snarkjs. The fixture is not committed yet: run testdata/plonk/generate.sh,
+// commit its output and drop the ignore
+#[test]
+#[ignore = "needs the snarkjs fixture from testdata/plonk/generate.sh"]
+fn test_snarkjs_plonk_fixture() {
+    let mut fixture = Fixture::new();
+    let read_fixture = |name: &str| {
+        let path = format!("{}/testdata/plonk/{}", env!("CARGO_MANIFEST_DIR"), name);
+        std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("cannot read {} ({})", path, err))
+    };
+    let vkey: PlonkVkey = serde_json::from_str(&read_fixture("verification_key.json")).unwrap();
+    let proof = read_fixture("proof.json");
+    let public: Vec<String> = serde_json::from_str(&read_fixture("public.json")).unwrap();
+    assert_eq!(public, vec!["33".to_string()]);
+
+    let _ = fixture.execute(ADMIN, register_plonk_msg("multiplier2", vkey, None)).unwrap();
+
+    let response = fixture.verify("multiplier2", &proof, public.into_iter().map(PublicInput::Decimal).collect());
+    assert!(response.valid);
+    assert_eq!(response.failure, None);
+
+    let response = fixture.verify("multiplier2", &proof, vec![decimal(34)]);
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+}
@@ -0,0 +1,141 @@
+// Random and mutated proofs through the parsers and the VerifyProof query. The fuzz target in
+// fuzz/ covers the same entry points with coverage guided input.
+use proptest::collection::{hash_map, vec};
+use proptest::prelude::*;
+use proptest::sample::Index;
+use serde_json::{json, Value};
+use super::*;
+use crate::{bn254, plonk, state};
+
+fn arb_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<u8>().prop_map(Value::from),
+        "[0-9]{0,100}".prop_map(Value::String),
+        ".*".prop_map(Value::String),
+    ];
+    leaf.prop_recursive(4, 256, 8, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 0..8).prop_map(Value::Array),
+            hash_map("pi_a|pi_b|pi_c|encoding|protocol|curve|[a-z_]{1,8}", inner, 0..6)
+                .prop_map(|fields| Value::Object(fields.into_iter().collect())),
+        ]
+    })
+}
+
+// ProofStr shaped, with points of any length and content
+fn arb_proof_str() -> impl Strategy<Value = Value> {
+    (vec(any::<u8>(), 0..200), vec(any::<u8>(), 0..400), vec(any::<u8>(), 0..200), any::<bool>()).prop_map(
+        |(pi_a, pi_b, pi_c, compressed)| {
+            let encoding = if compressed { "compressed" } else { "uncompressed" };
+            json!({ "encoding": encoding, "pi_a": pi_a, "pi_b": pi_b, "pi_c": pi_c })
+        },
+    )
+}
+
+// snarkjs proof.json shaped, with coordinates of any length and digits
+fn arb_snarkjs_proof() -> impl Strategy<Value = Value> {
+    let coords = || vec("[0-9]{0,120}", 0..4);
+    (coords(), vec(coords(), 0..4), coords(), "groth16|plonk|.*", "bls12381|bn128|.*").prop_map(
+        |(pi_a, pi_b, pi_c, protocol, curve)| {
+            json!({ "protocol": protocol, "curve": curve, "pi_a": pi_a, "pi_b": pi_b, "pi_c": pi_c })
+        },
+    )
+}
+
+fn arb_proof() -> impl Strategy<Value = String> {
+    prop_oneof![
+        ".*",
+        vec(any::<u8>(), 0..512).prop_map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+        arb_json().prop_map(|value| value.to_string()),
+        arb_proof_str().prop_map(|value| value.to_string()),
+        arb_snarkjs_proof().prop_map(|value| value.to_string()),
+    ]
+}
+
+fn arb_public_input() -> impl Strategy<Value = PublicInput> {
+    prop_oneof![
+        "[0-9]{0,80}|.*".prop_map(PublicInput::Decimal),
+        "(0x)?[0-9a-f]{0,70}|.*".prop_map(PublicInput::HexLe),
+        "(0x)?[0-9a-f]{0,70}|.*".prop_map(PublicInput::HexBe),
+    ]
+}
+
+// A contract with a freshly set up one input multiplier circuit registered as "multiply"
+fn registered_fixture() -> (Fixture, Parameters<Bls12>) {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(1);
+    fixture.register("multiply", to_vkey_str(&params));
+    (fixture, params)
+}
+
+thread_local! {
+    // Cases only query the contract, so they share one
+    static REGISTERED: (Fixture, Parameters<Bls12>) = registered_fixture();
+}
+
+fn verify(fixture: &Fixture, proof: String, public_inputs: Vec<PublicInput>) -> Result<VerifyProofResponse, ContractError> {
+    fixture.query(verify_msg("multiply", &proof, public_inputs))
+}
+
+proptest! {
+    #[test]
+    fn test_parsers_never_panic(proof in arb_proof()) {
+        for curve in [Curve::Bls12_381, Curve::Bn254] {
+            if let Ok(proof_str) = state::decode_proof(&proof, curve) {
+                match curve {
+                    Curve::Bls12_381 => drop(state::parse_proof(&proof_str)),
+                    Curve::Bn254 => drop(bn254::parse_proof(&proof_str)),
+                }
+            }
+        }
+        let _ = plonk::decode_proof(&proof);
+    }
+
+    #[test]
+    fn test_verify_query_never_panics(proof in arb_proof(), public_inputs in vec(arb_public_input(), 0..3)) {
+        // Malformed proofs are answered with a failure reason, random ones never verify
+        let response = REGISTERED.with(|(fixture, _)| verify(fixture, proof, public_inputs)).unwrap();
+        prop_assert!(!response.valid);
+    }
+}
+
+proptest! {
+    // Every case runs the prover
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn test_valid_proofs_verify_and_mutations_fail(
+        a in any::<u32>(),
+        b in any::<u32>(),
+        compressed in any::<bool>(),
+        point in 0..3usize,
+        byte in any::<Index>(),
+        bit in 0..8u8,
+    ) {
+        let encoding = if compressed { PointEncoding::Compressed } else { PointEncoding::Uncompressed };
+        let pairs = [(Scalar::from(a as u64), Scalar::from(b as u64))];
+        let proof = REGISTERED.with(|(_, params)| prove_multiply_demo_with_encoding(params, &pairs, encoding));
+        let product = decimal(a as u64 * b as u64);
+
+        let verify = |proof: String, public_input: PublicInput| {
+            REGISTERED.with(|(fixture, _)| verify(fixture, proof, vec![public_input])).unwrap()
+        };
+        prop_assert!(verify(proof.clone(), product.clone()).valid);
+
+        let other_product = decimal(a as u64 * b as u64 + 1);
+        prop_assert!(!verify(proof.clone(), other_product).valid);
+
+        let mut mutated: ProofStr = serde_json::from_str(&proof).unwrap();
+        let bytes = match point {
+            0 => &mut mutated.pi_a,
+            1 => &mut mutated.pi_b,
+            _ => &mut mutated.pi_c,
+        };
+        let index = byte.index(bytes.len());
+        edit_bytes(bytes, |bytes| bytes[index] ^= 1 << bit);
+        prop_assert!(!verify(serde_json::to_string(&mutated).unwrap(), product).valid);
+    }
+}
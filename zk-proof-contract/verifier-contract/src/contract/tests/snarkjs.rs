@@ -0,0 +1,67 @@
+use super::*;
+use crate::snarkjs::SnarkjsVkey;
+
+// Inverse of the snarkjs import, for checking that the fixture round-trips
+fn be_bytes_to_decimal(bytes: &[u8]) -> String {
+    let mut digits = Vec::new();
+    let mut value = bytes.to_vec();
+    while value.iter().any(|byte| *byte != 0) {
+        let mut remainder = 0u32;
+        for byte in value.iter_mut() {
+            let acc = (remainder << 8) | *byte as u32;
+            *byte = (acc / 10) as u8;
+            remainder = acc % 10;
+        }
+        digits.push(char::from_digit(remainder, 10).unwrap());
+    }
+    if digits.is_empty() {
+        digits.push('0');
+    }
+    digits.iter().rev().collect()
+}
+
+fn to_snarkjs_g1(bytes: &[u8]) -> Vec<String> {
+    vec![be_bytes_to_decimal(&bytes[..48]), be_bytes_to_decimal(&bytes[48..]), "1".to_string()]
+}
+
+fn to_snarkjs_g2(bytes: &[u8]) -> Vec<Vec<String>> {
+    vec![
+        vec![be_bytes_to_decimal(&bytes[48..96]), be_bytes_to_decimal(&bytes[..48])],
+        vec![be_bytes_to_decimal(&bytes[144..]), be_bytes_to_decimal(&bytes[96..144])],
+        vec!["1".to_string(), "0".to_string()],
+    ]
+}
+
+#[test]
+fn test_snarkjs_multiplier2_fixture() {
+    let mut fixture = Fixture::new();
+    let vkey: SnarkjsVkey = serde_json::from_str(include_str!("../../../testdata/multiplier2_verification_key.json")).unwrap();
+    let proof = include_str!("../../../testdata/multiplier2_proof.json");
+    let public: Vec<String> = serde_json::from_str(include_str!("../../../testdata/multiplier2_public.json")).unwrap();
+
+    // snarkjs -> VkeyStr -> snarkjs gives back the same coordinates
+    let vkey_str = vkey.to_vkey_str().unwrap();
+    assert_eq!(to_snarkjs_g1(&vkey_str.alpha_1), vkey.vk_alpha_1);
+    assert_eq!(to_snarkjs_g2(&vkey_str.beta_2), vkey.vk_beta_2);
+    assert_eq!(to_snarkjs_g2(&vkey_str.gamma_2), vkey.vk_gamma_2);
+    assert_eq!(to_snarkjs_g2(&vkey_str.delta_2), vkey.vk_delta_2);
+    assert_eq!(vkey_str.ic.iter().map(|point| to_snarkjs_g1(point)).collect::<Vec<_>>(), vkey.ic);
+
+    let register_msg = |circuit_id: &str, vkey: SnarkjsVkey| ExecuteMsg::RegisterSnarkjsVerifyingKey {
+        circuit_id: circuit_id.to_string(),
+        vkey,
+        nullifier: None,
+        grace_period: None,
+    };
+    let _ = fixture.execute(ADMIN, register_msg("multiplier2", vkey.clone())).unwrap();
+
+    assert!(fixture.verify("multiplier2", proof, public.into_iter().map(PublicInput::Decimal).collect()).valid);
+    let response = fixture.verify("multiplier2", proof, vec![decimal(34)]);
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+
+    // Keys for other curves are rejected up front
+    let mut bls12_377_vkey = vkey;
+    bls12_377_vkey.curve = "bls12377".to_string();
+    let err = fixture.execute(ADMIN, register_msg("multiplier2_bls12377", bls12_377_vkey)).unwrap_err();
+    assert!(matches!(err, ContractError::UnsupportedCurve { curve } if curve == "bls12377"));
+}
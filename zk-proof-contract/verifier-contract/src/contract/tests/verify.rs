@@ -0,0 +1,341 @@
+use bellman::groth16::Proof;
+use ff::PrimeField;
+use ark_groth16::Groth16;
+use ark_relations::lc;
+use ark_relations::r1cs::{self, ConstraintSynthesizer, ConstraintSystemRef};
+use ark_snark::SNARK;
+use super::*;
+use crate::msg::{VerifyProofBatchResponse, VerifyTruthResponse};
+use crate::pairing::{NativeBackend, PairingBackend, RustBackend};
+use crate::state::{PreparedVerifyingKey, PREPARED_KEYS, batch_verify_proofs, verify_proof};
+
+#[test]
+fn test_verify_truth_invalid_case() {
+    let fixture = Fixture::new();
+
+    // Invalid Inputs
+    let response: VerifyTruthResponse = fixture.query(QueryMsg::VerifyTruth { a: 1, b: 2, sum: 3 }).unwrap();
+    assert_eq!(response, VerifyTruthResponse { valid: false });
+}
+
+#[test]
+fn test_verify_truth_valid_case() {
+    let fixture = Fixture::new();
+
+    // Valid inputs
+    let response: VerifyTruthResponse = fixture.query(QueryMsg::VerifyTruth { a: 10, b: 2, sum: 12 }).unwrap();
+    assert_eq!(response, VerifyTruthResponse { valid: true });
+}
+
+#[test]
+fn test_verify_proof_with_registered_key() {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(1);
+    fixture.register("multiply", to_vkey_str(&params));
+    let proof = prove_multiply_demo(&params, &[(Scalar::from(3), Scalar::from(4))]);
+
+    assert_eq!(
+        fixture.verify("multiply", &proof, vec![decimal(12)]),
+        VerifyProofResponse {
+            valid: true,
+            circuit_id: "multiply".to_string(),
+            key_version: 1,
+            public_inputs: vec![decimal(12)],
+            failure: None,
+        }
+    );
+
+    let response = fixture.verify("multiply", &proof, vec![decimal(13)]);
+    assert!(!response.valid);
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+
+    // Registering again under the same id bumps the key version
+    let response = fixture.register("multiply", to_vkey_str(&params));
+    assert_eq!(response.attributes[2].value, "2");
+    assert_eq!(fixture.verify("multiply", &proof, vec![decimal(12)]).key_version, 2);
+
+    // No key registered under this id
+    assert!(fixture.query::<VerifyProofResponse>(verify_msg("unknown", &proof, vec![decimal(12)])).is_err());
+}
+
+#[test]
+fn test_prepared_key_storage() {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(2);
+    fixture.register("multiply", to_vkey_str(&params));
+
+    let stored = PREPARED_KEYS.load(&fixture.deps.storage, ("multiply", 1)).unwrap();
+    let pvk = PreparedVerifyingKey::from_bytes(&stored).unwrap();
+    assert_eq!(pvk, PreparedVerifyingKey::new(&params.vk));
+    assert_eq!(pvk.verifying_key().ic, params.vk.ic);
+
+    let proof = prove_multiply_demo(&params, &[(Scalar::from(3), Scalar::from(4)), (Scalar::from(5), Scalar::from(6))]);
+    assert!(fixture.verify("multiply", &proof, vec![decimal(12), decimal(30)]).valid);
+
+    // A corrupted prepared key fails loudly instead of verifying against garbage
+    PREPARED_KEYS.save(&mut fixture.deps.storage, ("multiply", 1), &Binary::from(&stored[..100])).unwrap();
+    let err = fixture.query::<VerifyProofResponse>(verify_msg("multiply", &proof, vec![decimal(12), decimal(30)])).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidVerifyingKey {}));
+}
+
+#[test]
+fn test_verify_proof_with_multiple_public_inputs() {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(3);
+    fixture.register("multiply_3", to_vkey_str(&params));
+    let proof = prove_multiply_demo(
+        &params,
+        &[(Scalar::from(3), Scalar::from(4)), (Scalar::from(5), Scalar::from(6)), (Scalar::from(7), Scalar::from(8))],
+    );
+
+    assert!(fixture.verify("multiply_3", &proof, vec![decimal(12), decimal(30), decimal(56)]).valid);
+
+    // Inputs in the wrong order
+    let response = fixture.verify("multiply_3", &proof, vec![decimal(30), decimal(12), decimal(56)]);
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+
+    let response = fixture.verify("multiply_3", &proof, vec![decimal(12), decimal(30)]);
+    assert_eq!(response.failure, Some(VerificationFailure::InputMismatch { expected: 3, actual: 2 }));
+}
+
+#[test]
+fn test_verify_proof_with_field_element_inputs() {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(1);
+    fixture.register("multiply", to_vkey_str(&params));
+
+    // Product far beyond u64, like a hash or Merkle root would be
+    let a = Scalar::from(u64::MAX).square();
+    let b = Scalar::from(u64::MAX - 1).square();
+    let proof = prove_multiply_demo(&params, &[(a, b)]);
+    let c_le = (a * b).to_repr();
+    let mut c_be = c_le;
+    c_be.reverse();
+
+    for public_input in [
+        PublicInput::HexLe(hex::encode(c_le)),
+        PublicInput::HexBe(format!("0x{}", hex::encode(c_be))),
+    ] {
+        assert!(fixture.verify("multiply", &proof, vec![public_input]).valid);
+    }
+
+    // Field modulus, in decimal and big-endian hex, along with a value wider than 256 bits
+    for public_input in [
+        PublicInput::Decimal(
+            "52435875175126190479447740508185965837690552500527637822603658699938581184513".to_string(),
+        ),
+        PublicInput::HexBe("73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001".to_string()),
+        PublicInput::Decimal("9".repeat(78)),
+        PublicInput::HexLe("0102".to_string()),
+        PublicInput::Decimal("-1".to_string()),
+    ] {
+        let response = fixture.verify("multiply", &proof, vec![public_input]);
+        assert_eq!(response.failure, parse_error(ContractError::InvalidPublicInput { index: 0 }));
+    }
+}
+
+#[test]
+fn test_verify_malformed_proof() {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(1);
+    fixture.register("multiply", to_vkey_str(&params));
+    let proof = prove_multiply_demo(&params, &[(Scalar::from(3), Scalar::from(4))]);
+    let valid_proof: ProofStr = serde_json::from_str(&proof).unwrap();
+
+    let query_with_proof = |proof: String| {
+        let response = fixture.verify("multiply", &proof, vec![decimal(12)]);
+        assert!(!response.valid);
+        response.failure
+    };
+
+    let failure = query_with_proof("{\"pi_a\": [1, 2".to_string());
+    assert!(matches!(failure, Some(VerificationFailure::ParseError { msg }) if msg.starts_with("Invalid proof JSON")));
+
+    // Truncated pi_a
+    let mut truncated = valid_proof.clone();
+    edit_bytes(&mut truncated.pi_a, |bytes| bytes.truncate(48));
+    let failure = query_with_proof(serde_json::to_string(&truncated).unwrap());
+    let err = ContractError::InvalidPointLength { field: "pi_a".to_string(), expected: 96, actual: 48 };
+    assert_eq!(failure, parse_error(err));
+
+    // Oversized pi_b
+    let mut oversized = valid_proof.clone();
+    edit_bytes(&mut oversized.pi_b, |bytes| bytes.push(0));
+    let failure = query_with_proof(serde_json::to_string(&oversized).unwrap());
+    let err = ContractError::InvalidPointLength { field: "pi_b".to_string(), expected: 192, actual: 193 };
+    assert_eq!(failure, parse_error(err));
+
+    // pi_c with its y coordinate nudged off the curve
+    let mut off_curve = valid_proof.clone();
+    edit_bytes(&mut off_curve.pi_c, |bytes| bytes[95] ^= 1);
+    let failure = query_with_proof(serde_json::to_string(&off_curve).unwrap());
+    assert_eq!(failure, parse_error(ContractError::PointNotOnCurve { field: "pi_c".to_string() }));
+
+    // Compression flag set on an uncompressed point
+    let mut bad_encoding = valid_proof;
+    edit_bytes(&mut bad_encoding.pi_a, |bytes| bytes[0] |= 0x80);
+    let failure = query_with_proof(serde_json::to_string(&bad_encoding).unwrap());
+    assert_eq!(failure, parse_error(ContractError::InvalidPointEncoding { field: "pi_a".to_string() }));
+}
+
+#[test]
+fn test_verify_compressed_proof() {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(1);
+    fixture.register("multiply", to_compressed_vkey_str(&params));
+
+    // Key and proof encodings are independent of each other
+    for encoding in [PointEncoding::Compressed, PointEncoding::Uncompressed] {
+        let proof = prove_multiply_demo_with_encoding(&params, &[(Scalar::from(3), Scalar::from(4))], encoding);
+        assert!(fixture.verify("multiply", &proof, vec![decimal(12)]).valid);
+    }
+
+    // Uncompressed bytes under the compressed encoding
+    let proof = prove_multiply_demo(&params, &[(Scalar::from(3), Scalar::from(4))]);
+    let mut mislabeled: ProofStr = serde_json::from_str(&proof).unwrap();
+    mislabeled.encoding = PointEncoding::Compressed;
+    let response = fixture.verify("multiply", &serde_json::to_string(&mislabeled).unwrap(), vec![decimal(12)]);
+    let err = ContractError::InvalidPointLength { field: "pi_a".to_string(), expected: 48, actual: 96 };
+    assert_eq!(response.failure, parse_error(err));
+}
+
+#[test]
+fn test_legacy_proof_format() {
+    let params = setup_multiply_demo(1);
+    let proof = prove_multiply_demo(&params, &[(Scalar::from(3), Scalar::from(4))]);
+    let proof_str: ProofStr = serde_json::from_str(&proof).unwrap();
+
+    // No encoding field, points as integer arrays
+    let legacy_proof = serde_json::json!({
+        "pi_a": proof_str.pi_a.to_vec(),
+        "pi_b": proof_str.pi_b.to_vec(),
+        "pi_c": proof_str.pi_c.to_vec(),
+    });
+    let parsed: ProofStr = serde_json::from_value(legacy_proof).unwrap();
+    assert_eq!(parsed, proof_str);
+}
+
+// c <- a * b over BN254, built with arkworks the way circom circuits for EVM verifiers are
+struct Bn254MultiplyDemo {
+    a: Option<ark_bn254::Fr>,
+    b: Option<ark_bn254::Fr>,
+}
+
+impl ConstraintSynthesizer<ark_bn254::Fr> for Bn254MultiplyDemo {
+    fn generate_constraints(self, cs: ConstraintSystemRef<ark_bn254::Fr>) -> r1cs::Result<()> {
+        let a = cs.new_witness_variable(|| self.a.ok_or(r1cs::SynthesisError::AssignmentMissing))?;
+        let b = cs.new_witness_variable(|| self.b.ok_or(r1cs::SynthesisError::AssignmentMissing))?;
+        let c = cs.new_input_variable(|| {
+            Ok(self.a.ok_or(r1cs::SynthesisError::AssignmentMissing)? * self.b.ok_or(r1cs::SynthesisError::AssignmentMissing)?)
+        })?;
+        cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)
+    }
+}
+
+#[test]
+fn test_verify_bn254_proof() {
+    let mut fixture = Fixture::new();
+    let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(Bn254MultiplyDemo { a: None, b: None }, &mut OsRng).unwrap();
+    let circuit = Bn254MultiplyDemo { a: Some(ark_bn254::Fr::from(3u64)), b: Some(ark_bn254::Fr::from(4u64)) };
+    let proof = bn254_proof_str(&Groth16::<Bn254>::prove(&pk, circuit, &mut OsRng).unwrap());
+    let vkey = bn254_vkey_str(&vk);
+    fixture.register("multiply_bn254", vkey.clone());
+
+    assert!(fixture.verify("multiply_bn254", &proof, vec![decimal(12)]).valid);
+    let response = fixture.verify("multiply_bn254", &proof, vec![decimal(13)]);
+    assert_eq!(response.failure, Some(VerificationFailure::PairingFailure));
+
+    // BN254 scalar field modulus
+    let modulus = PublicInput::Decimal(
+        "21888242871839275222246405745257275088548364400416034343698204186575808495617".to_string(),
+    );
+    let response = fixture.verify("multiply_bn254", &proof, vec![modulus]);
+    assert_eq!(response.failure, parse_error(ContractError::InvalidPublicInput { index: 0 }));
+
+    // A BLS12-381 proof is the wrong size for a BN254 key
+    let bls_proof = prove_multiply_demo(&setup_multiply_demo(1), &[(Scalar::from(3), Scalar::from(4))]);
+    let response = fixture.verify("multiply_bn254", &bls_proof, vec![decimal(12)]);
+    let err = ContractError::InvalidPointLength { field: "pi_a".to_string(), expected: 64, actual: 96 };
+    assert_eq!(response.failure, parse_error(err));
+
+    let mut compressed_vkey = vkey;
+    compressed_vkey.encoding = PointEncoding::Compressed;
+    let err = fixture.execute(ADMIN, register_msg("multiply_bn254_compressed", compressed_vkey)).unwrap_err();
+    assert!(matches!(err, ContractError::UnsupportedEncoding {}));
+}
+
+#[test]
+fn test_verify_proof_batch() {
+    let mut fixture = Fixture::new();
+    let params = setup_multiply_demo(1);
+    fixture.register("multiply", to_vkey_str(&params));
+
+    let mut proofs: Vec<(String, Vec<PublicInput>)> = (1..=5u64)
+        .map(|a| (prove_multiply_demo(&params, &[(Scalar::from(a), Scalar::from(7))]), vec![decimal(a * 7)]))
+        .collect();
+    let batch_msg = |proofs: Vec<(String, Vec<PublicInput>)>| QueryMsg::VerifyProofBatch {
+        circuit_id: "multiply".to_string(),
+        proofs,
+        key_version: None,
+    };
+
+    let response: VerifyProofBatchResponse = fixture.query(batch_msg(proofs.clone())).unwrap();
+    assert_eq!(response, VerifyProofBatchResponse { all_valid: true, results: vec![true; 5] });
+
+    // A single wrong input fails the batch, and is pinpointed in the results
+    proofs[3].1 = vec![decimal(29)];
+    let response: VerifyProofBatchResponse = fixture.query(batch_msg(proofs.clone())).unwrap();
+    assert_eq!(
+        response,
+        VerifyProofBatchResponse { all_valid: false, results: vec![true, true, true, false, true] }
+    );
+
+    // Malformed items and wrong input counts are false, the other proofs are still checked
+    proofs[1].1 = vec![];
+    proofs[2].0 = "not a proof".to_string();
+    let mut truncated: ProofStr = serde_json::from_str(&proofs[4].0).unwrap();
+    edit_bytes(&mut truncated.pi_c, |bytes| {
+        bytes.pop();
+    });
+    proofs.push((serde_json::to_string(&truncated).unwrap(), vec![decimal(35)]));
+    let response: VerifyProofBatchResponse = fixture.query(batch_msg(proofs)).unwrap();
+    assert_eq!(
+        response,
+        VerifyProofBatchResponse { all_valid: false, results: vec![true, false, false, false, true, false] }
+    );
+
+    let err = fixture.query::<VerifyProofBatchResponse>(batch_msg(vec![])).unwrap_err();
+    assert!(matches!(err, ContractError::EmptyProofBatch {}));
+}
+
+fn check_pairing_backend<B: PairingBackend>(backend: &B, params: &Parameters<Bls12>) {
+    let pvk = PreparedVerifyingKey::new(&params.vk);
+    let items: Vec<(Proof<Bls12>, Vec<Scalar>)> = (1..=3u64)
+        .map(|a| {
+            let circuit = MultiplyDemo { pairs: vec![(Some(Scalar::from(a)), Some(Scalar::from(5)))] };
+            (create_random_proof(circuit, params, &mut OsRng).unwrap(), vec![Scalar::from(a * 5)])
+        })
+        .collect();
+
+    assert!(verify_proof(backend, &pvk, &items[0].0, &items[0].1).unwrap());
+    assert!(!verify_proof(backend, &pvk, &items[0].0, &[Scalar::from(6)]).unwrap());
+    assert!(!verify_proof(backend, &pvk, &items[0].0, &[]).unwrap());
+    assert_eq!(batch_verify_proofs(backend, &pvk, &items).unwrap(), vec![true; 3]);
+
+    let mut items = items;
+    items[1].1 = vec![Scalar::from(11)];
+    assert_eq!(batch_verify_proofs(backend, &pvk, &items).unwrap(), vec![true, false, true]);
+}
+
+#[test]
+fn test_pairing_backends() {
+    let params = setup_multiply_demo(1);
+    let api = MockApi::default();
+    check_pairing_backend(&RustBackend, &params);
+    check_pairing_backend(&NativeBackend::new(&api), &params);
+
+    // MockApi rejects what the host rejects, such as an empty sum, and the error reaches the caller
+    let err = NativeBackend::new(&api).aggregate_g1(&[]).unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+    assert_eq!(NativeBackend::new(&api).aggregate_g1(&params.vk.ic).unwrap(), RustBackend.aggregate_g1(&params.vk.ic).unwrap());
+}
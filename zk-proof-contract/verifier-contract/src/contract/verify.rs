@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Binary, Deps, Env, to_json_binary};
+
+use crate::bn254;
+use crate::error::ContractError;
+use crate::msg::{VerificationFailure, VerifyProofResponse, VerifyProofBatchResponse};
+use crate::pairing;
+use crate::plonk;
+use crate::state::{
+    CIRCUITS, PLONK_KEYS, PREPARED_KEYS, VERIFYING_KEYS, Curve, PreparedVerifyingKey, ProofStr, ProofSystem, PublicInput,
+    VkeyStr, batch_verify_proofs, decode_proof, get_public_signal, parse_proof, verify_proof,
+};
+
+use super::keys::{candidate_versions, pin_key_hash};
+
+// Groth16 and PLONK proof verification against a circuit's key versions, one proof or a batch
+
+pub(super) fn verify_the_zk_proof(
+    deps: Deps,
+    env: Env,
+    circuit_id: String,
+    proof: String,
+    public_inputs: Vec<PublicInput>,
+    key_version: Option<u64>,
+    expected_vkey_hash: Option<String>,
+) -> Result<Binary, ContractError> {
+    let mut versions = candidate_versions(deps, &env, &circuit_id, key_version)?;
+    if let Some(expected_vkey_hash) = expected_vkey_hash {
+        versions = pin_key_hash(deps, &circuit_id, versions, &expected_vkey_hash)?;
+    }
+    let (key_version, failure) = match verify_against_versions(deps, &circuit_id, &versions, &proof, &public_inputs) {
+        Ok((version, _)) => (version, None),
+        Err(err) => (versions[0], Some(failure_reason(err)?)),
+    };
+
+    Ok(to_json_binary(&VerifyProofResponse {
+        valid: failure.is_none(),
+        circuit_id,
+        key_version,
+        public_inputs,
+        failure,
+    })?)
+}
+
+// Orders named_inputs by the circuit's input names. Every name must be given exactly once, and
+// positional inputs cannot be mixed in
+pub(super) fn positional_inputs(
+    deps: Deps,
+    circuit_id: &str,
+    public_inputs: Vec<PublicInput>,
+    mut named_inputs: BTreeMap<String, PublicInput>,
+) -> Result<Vec<PublicInput>, ContractError> {
+    if !public_inputs.is_empty() {
+        return Err(ContractError::MixedPublicInputs {});
+    }
+    let input_names = CIRCUITS
+        .load(deps.storage, circuit_id)?
+        .input_names
+        .ok_or_else(|| ContractError::NoInputNames { circuit_id: circuit_id.to_string() })?;
+
+    let mut positional = Vec::new();
+    for name in input_names.names() {
+        match named_inputs.remove(&name) {
+            Some(value) => positional.push(value),
+            None => return Err(ContractError::MissingInputName { name }),
+        }
+    }
+    if let Some(name) = named_inputs.into_keys().next() {
+        return Err(ContractError::UnknownInputName { name });
+    }
+    Ok(positional)
+}
+
+pub(super) fn failure_reason(err: ContractError) -> Result<VerificationFailure, ContractError> {
+    match err {
+        ContractError::InvalidProof {} => Ok(VerificationFailure::PairingFailure),
+        ContractError::PublicInputCountMismatch { expected, actual } => {
+            Ok(VerificationFailure::InputMismatch { expected, actual })
+        }
+        err if err.is_malformed_input() => Ok(VerificationFailure::ParseError { msg: err.to_string() }),
+        err => Err(err),
+    }
+}
+
+// Stops at the first version the proof verifies against, returning that version and the proof
+// points as verify_decoded_proof does. When it verifies against none, fails the way the first
+// version did. Storage errors are returned right away.
+pub(super) fn verify_against_versions(
+    deps: Deps,
+    circuit_id: &str,
+    versions: &[u64],
+    proof: &str,
+    public_inputs: &[PublicInput],
+) -> Result<(u64, Vec<u8>), ContractError> {
+    let mut first_rejection = None;
+    for version in versions.iter() {
+        match verify_against_version(deps, circuit_id, *version, proof, public_inputs) {
+            Ok(proof_bytes) => return Ok((*version, proof_bytes)),
+            Err(err) if err.is_proof_rejection() => {
+                first_rejection.get_or_insert(err);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Err(first_rejection.unwrap_or(ContractError::NoUsableKey { circuit_id: circuit_id.to_string() }))
+}
+
+fn verify_against_version(
+    deps: Deps,
+    circuit_id: &str,
+    version: u64,
+    proof: &str,
+    public_inputs: &[PublicInput],
+) -> Result<Vec<u8>, ContractError> {
+    if CIRCUITS.load(deps.storage, circuit_id)?.proof_system == ProofSystem::Plonk {
+        return verify_plonk_proof(deps, circuit_id, version, proof, public_inputs);
+    }
+
+    let vkey = VERIFYING_KEYS.load(deps.storage, (circuit_id, version))?;
+    check_public_input_count(vkey.ic.len() - 1, public_inputs)?;
+
+    let proof = decode_proof(proof, vkey.curve)?;
+    let prepared_key = PREPARED_KEYS.load(deps.storage, (circuit_id, version))?;
+    let (valid, proof_bytes) = verify_decoded_proof(deps, &vkey, &prepared_key, &proof, public_inputs)?;
+    if !valid {
+        return Err(ContractError::InvalidProof {});
+    }
+    Ok(proof_bytes)
+}
+
+// Same contract as verify_against_version, the returned bytes being the proof in plonk.rs layout
+fn verify_plonk_proof(
+    deps: Deps,
+    circuit_id: &str,
+    version: u64,
+    proof: &str,
+    public_inputs: &[PublicInput],
+) -> Result<Vec<u8>, ContractError> {
+    let vkey = PLONK_KEYS.load(deps.storage, (circuit_id, version))?;
+    check_public_input_count(vkey.n_public, public_inputs)?;
+
+    let proof = plonk::decode_proof(proof)?;
+    let public_signal = bn254::get_public_signal(public_inputs)?;
+    let prepared_key = PREPARED_KEYS.load(deps.storage, (circuit_id, version))?;
+    let vk = plonk::VerifyingKey::from_bytes(&prepared_key)?;
+    if !plonk::verify_proof(&vk, &proof, &public_signal) {
+        return Err(ContractError::InvalidProof {});
+    }
+    Ok(proof.to_bytes())
+}
+
+// Also returns the proof points in uncompressed form, so that a proof hashes the same however it
+// was encoded
+fn verify_decoded_proof(
+    deps: Deps,
+    vkey: &VkeyStr,
+    prepared_key: &[u8],
+    proof: &ProofStr,
+    public_inputs: &[PublicInput],
+) -> Result<(bool, Vec<u8>), ContractError> {
+    match vkey.curve {
+        Curve::Bls12_381 => {
+            let pvk = PreparedVerifyingKey::from_bytes(prepared_key)?;
+            let parsed_proof = parse_proof(proof)?;
+            let public_signal = get_public_signal(public_inputs)?;
+            let proof_bytes = [
+                parsed_proof.a.to_uncompressed().as_ref(),
+                parsed_proof.b.to_uncompressed().as_ref(),
+                parsed_proof.c.to_uncompressed().as_ref(),
+            ]
+            .concat();
+            let valid = verify_proof(&pairing::default_backend(deps.api), &pvk, &parsed_proof, &public_signal)?;
+            Ok((valid, proof_bytes))
+        }
+        // BN254 points have a single encoding
+        Curve::Bn254 => {
+            let pvk = bn254::load_prepared_key(prepared_key)?;
+            let parsed_proof = bn254::parse_proof(proof)?;
+            let public_signal = bn254::get_public_signal(public_inputs)?;
+            let proof_bytes = [proof.pi_a.as_slice(), proof.pi_b.as_slice(), proof.pi_c.as_slice()].concat();
+            Ok((bn254::verify_proof(&pvk, &parsed_proof, &public_signal), proof_bytes))
+        }
+    }
+}
+
+pub(super) fn verify_the_zk_proof_batch(
+    deps: Deps,
+    env: Env,
+    circuit_id: String,
+    proofs: Vec<(String, Vec<PublicInput>)>,
+    key_version: Option<u64>,
+) -> Result<Binary, ContractError> {
+    if proofs.is_empty() {
+        return Err(ContractError::EmptyProofBatch {});
+    }
+
+    // The batch is checked against the first version. Proofs it rejects are tried against the other
+    // versions one by one, as VerifyProof would, so proofs made just before a rotation still pass
+    let versions = candidate_versions(deps, &env, &circuit_id, key_version)?;
+    let mut results = match CIRCUITS.load(deps.storage, &circuit_id)?.proof_system {
+        ProofSystem::Groth16 => verify_groth16_batch(deps, &circuit_id, versions[0], &proofs)?,
+        // There is no batched PLONK check, so the proofs are checked one at a time
+        ProofSystem::Plonk => {
+            let mut results = Vec::new();
+            for (proof, public_inputs) in proofs.iter() {
+                match verify_plonk_proof(deps, &circuit_id, versions[0], proof, public_inputs) {
+                    Ok(_) => results.push(true),
+                    Err(err) if err.is_proof_rejection() => results.push(false),
+                    Err(err) => return Err(err),
+                }
+            }
+            results
+        }
+    };
+    let fallback_versions = &versions[1..];
+    for (valid, (proof, public_inputs)) in results.iter_mut().zip(proofs.iter()) {
+        if *valid || fallback_versions.is_empty() {
+            continue;
+        }
+        match verify_against_versions(deps, &circuit_id, fallback_versions, proof, public_inputs) {
+            Ok(_) => *valid = true,
+            Err(err) if err.is_proof_rejection() => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(to_json_binary(&VerifyProofBatchResponse {
+        all_valid: results.iter().all(|valid| *valid),
+        results,
+    })?)
+}
+
+// Malformed proofs and proofs with the wrong number of inputs are false, the others are checked
+// together
+fn verify_groth16_batch(
+    deps: Deps,
+    circuit_id: &str,
+    version: u64,
+    proofs: &[(String, Vec<PublicInput>)],
+) -> Result<Vec<bool>, ContractError> {
+    let vkey = VERIFYING_KEYS.load(deps.storage, (circuit_id, version))?;
+    let prepared_key = PREPARED_KEYS.load(deps.storage, (circuit_id, version))?;
+    let mut results = vec![false; proofs.len()];
+    match vkey.curve {
+        Curve::Bls12_381 => {
+            let pvk = PreparedVerifyingKey::from_bytes(&prepared_key)?;
+            let mut indices = Vec::new();
+            let mut items = Vec::new();
+            for (i, (proof, public_inputs)) in proofs.iter().enumerate() {
+                let item = check_public_input_count(vkey.ic.len() - 1, public_inputs)
+                    .and_then(|_| decode_proof(proof, vkey.curve))
+                    .and_then(|proof| Ok((parse_proof(&proof)?, get_public_signal(public_inputs)?)));
+                match item {
+                    Ok(item) => {
+                        indices.push(i);
+                        items.push(item);
+                    }
+                    Err(err) if err.is_proof_rejection() => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            if !items.is_empty() {
+                let valid = batch_verify_proofs(&pairing::default_backend(deps.api), &pvk, &items)?;
+                for (i, valid) in indices.into_iter().zip(valid) {
+                    results[i] = valid;
+                }
+            }
+        }
+        // arkworks has no batch verifier, so BN254 proofs are checked one at a time
+        Curve::Bn254 => {
+            let pvk = bn254::load_prepared_key(&prepared_key)?;
+            for (result, (proof, public_inputs)) in results.iter_mut().zip(proofs.iter()) {
+                let item = check_public_input_count(vkey.ic.len() - 1, public_inputs)
+                    .and_then(|_| decode_proof(proof, vkey.curve))
+                    .and_then(|proof| Ok((bn254::parse_proof(&proof)?, bn254::get_public_signal(public_inputs)?)));
+                match item {
+                    Ok((parsed_proof, public_signal)) => *result = bn254::verify_proof(&pvk, &parsed_proof, &public_signal),
+                    Err(err) if err.is_proof_rejection() => {}
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+    }
+    Ok(results)
+}
+
+// A Groth16 key's ic holds one point per public input, plus one for the constant "one" input
+fn check_public_input_count(expected: usize, public_inputs: &[PublicInput]) -> Result<(), ContractError> {
+    if public_inputs.len() != expected {
+        return Err(ContractError::PublicInputCountMismatch {
+            expected,
+            actual: public_inputs.len(),
+        });
+    }
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use cosmwasm_std::StdError;
+use contract_migration::MigrationError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -8,4 +9,180 @@ pub enum ContractError {
 
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
+
+    #[error("{0}")]
+    Migration(#[from] MigrationError),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("No admin has been proposed")]
+    NoPendingAdmin {},
+
+    #[error("Invalid verifying key")]
+    InvalidVerifyingKey {},
+
+    #[error("Expected {expected} public inputs, got {actual}")]
+    PublicInputCountMismatch { expected: usize, actual: usize },
+
+    #[error("Public input {index} is not a canonical field element")]
+    InvalidPublicInput { index: usize },
+
+    #[error("Invalid proof JSON: {msg}")]
+    InvalidProofJson { msg: String },
+
+    #[error("{field} must be {expected} bytes, got {actual}")]
+    InvalidPointLength { field: String, expected: usize, actual: usize },
+
+    #[error("{field} is not a valid point encoding")]
+    InvalidPointEncoding { field: String },
+
+    #[error("{field} is not on the curve")]
+    PointNotOnCurve { field: String },
+
+    #[error("{field} is not in the prime order subgroup")]
+    PointNotInSubgroup { field: String },
+
+    #[error("Unsupported protocol: {protocol}")]
+    UnsupportedProtocol { protocol: String },
+
+    #[error("Unsupported curve: {curve}")]
+    UnsupportedCurve { curve: String },
+
+    #[error("{field} has malformed coordinates")]
+    InvalidCoordinates { field: String },
+
+    #[error("{field} is not a canonical scalar")]
+    InvalidScalar { field: String },
+
+    #[error("Circuit {circuit_id} verifies proofs of another proof system")]
+    ProofSystemMismatch { circuit_id: String },
+
+    #[error("Compressed points are not supported on BN254")]
+    UnsupportedEncoding {},
+
+    #[error("Proof batch is empty")]
+    EmptyProofBatch {},
+
+    #[error("Invalid proof")]
+    InvalidProof {},
+
+    #[error("Nullifier public input {index} is out of range")]
+    InvalidNullifierIndex { index: usize },
+
+    #[error("Circuit {circuit_id} has no nullifier source, register a key with one first")]
+    NoNullifierSource { circuit_id: String },
+
+    #[error("Circuit {circuit_id} uses the proof hash as nullifier, which does not stop replays")]
+    ReplayableNullifier { circuit_id: String },
+
+    #[error("Circuit {circuit_id} has no messages hash input, set one with SetMsgsHashInput")]
+    NoMsgsHashInput { circuit_id: String },
+
+    #[error("Messages hash public input {index} is out of range")]
+    InvalidMsgsHashIndex { index: usize },
+
+    #[error("Public inputs do not commit to the messages, expected {expected} as messages hash")]
+    MsgsHashMismatch { expected: String },
+
+    #[error("Nullifier {nullifier} has already been used")]
+    NullifierAlreadyUsed { nullifier: String },
+
+    #[error("Circuit {circuit_id} has no key version {version}")]
+    UnknownKeyVersion { circuit_id: String, version: u64 },
+
+    #[error("Key version {version} is revoked or past its grace period")]
+    KeyVersionNotUsable { version: u64 },
+
+    #[error("Key version {version} is revoked")]
+    KeyRevoked { version: u64 },
+
+    #[error("Circuit {circuit_id} has no usable key")]
+    NoUsableKey { circuit_id: String },
+
+    #[error("Grace period ends past the largest block height or time")]
+    GracePeriodTooLong {},
+
+    #[error("Expected verifying key hash {expected}, got {actual}")]
+    VkeyHashMismatch { expected: String, actual: String },
+
+    #[error("Merkle tree {tree_id} already exists")]
+    MerkleTreeExists { tree_id: String },
+
+    #[error("Root index {index} is out of range")]
+    InvalidRootIndex { index: usize },
+
+    #[error("Root history size must be between 1 and {max}, got {size}")]
+    InvalidHistorySize { size: u32, max: u32 },
+
+    #[error("Merkle root is not a field element of the tree's curve")]
+    InvalidMerkleRoot {},
+
+    #[error("Merkle tree {tree_id} has no root yet")]
+    NoMerkleRoot { tree_id: String },
+
+    #[error("Merkle root {root} is not accepted")]
+    MerkleRootNotAccepted { root: String },
+
+    #[error("Group {group_id} already exists")]
+    GroupExists { group_id: String },
+
+    #[error("Group depth must be between 1 and {max}, got {depth}")]
+    InvalidGroupDepth { depth: u8, max: u8 },
+
+    #[error("Circuit {circuit_id} does not verify BN254 Groth16 proofs with 4 public inputs")]
+    InvalidGroupCircuit { circuit_id: String },
+
+    #[error("Identity commitment is not a canonical field element")]
+    InvalidIdentityCommitment {},
+
+    #[error("Group {group_id} is full")]
+    GroupFull { group_id: String },
+
+    #[error("Expected {expected} public input names, got {actual}")]
+    InputNamesMismatch { expected: usize, actual: usize },
+
+    #[error("The {template} template takes BN254 keys")]
+    TemplateCurveMismatch { template: String },
+
+    #[error("Public input name {name} is used twice")]
+    DuplicateInputName { name: String },
+
+    #[error("Circuit {circuit_id} has no public input names")]
+    NoInputNames { circuit_id: String },
+
+    #[error("Circuit has no public input named {name}")]
+    UnknownInputName { name: String },
+
+    #[error("Missing public input {name}")]
+    MissingInputName { name: String },
+
+    #[error("Public inputs are given both by position and by name")]
+    MixedPublicInputs {},
+}
+
+impl ContractError {
+    // Errors caused by a malformed proof or public input, as opposed to missing or corrupted state
+    pub fn is_malformed_input(&self) -> bool {
+        matches!(
+            self,
+            ContractError::InvalidPublicInput { .. }
+                | ContractError::InvalidProofJson { .. }
+                | ContractError::InvalidPointLength { .. }
+                | ContractError::InvalidPointEncoding { .. }
+                | ContractError::PointNotOnCurve { .. }
+                | ContractError::PointNotInSubgroup { .. }
+                | ContractError::UnsupportedProtocol { .. }
+                | ContractError::UnsupportedCurve { .. }
+                | ContractError::InvalidCoordinates { .. }
+                | ContractError::InvalidScalar { .. }
+                | ContractError::UnsupportedEncoding { .. }
+        )
+    }
+
+    // Reasons a submitted proof is turned down, as opposed to missing or corrupted state
+    pub fn is_proof_rejection(&self) -> bool {
+        self.is_malformed_input()
+            || matches!(self, ContractError::InvalidProof {} | ContractError::PublicInputCountMismatch { .. })
+    }
 }
@@ -1,6 +1,11 @@
+pub mod bn254;
 pub mod contract;
 mod error;
 pub mod msg;
+pub mod pairing;
+pub mod plonk;
+pub mod semaphore;
+pub mod snarkjs;
 pub mod state;
 
 pub use crate::error::ContractError;
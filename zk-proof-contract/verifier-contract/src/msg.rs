@@ -1,6 +1,15 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Addr, CosmosMsg, Uint256};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::plonk::PlonkVkey;
+use crate::snarkjs::SnarkjsVkey;
+use crate::state::{
+    CircuitTemplate, Curve, GracePeriod, InputNames, KeyStatus, NullifierSource, ProofSystem, PublicInput, VkeyStr,
+};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct InstantiateMsg {
@@ -10,7 +19,160 @@ pub struct InstantiateMsg {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    CustomMsg { val: String },
+    // Admin only. Adds the next key version for circuit_id and makes it the active one. The previous
+    // active key is deprecated for grace_period, so proofs made for it keep verifying until then
+    RegisterVerifyingKey {
+        circuit_id: String,
+        vkey: VkeyStr,
+        // What SubmitProof records for this circuit. A circuit registered without one does not take
+        // SubmitProof until a later key sets it. When omitted, an existing circuit keeps its setting
+        #[serde(default)]
+        nullifier: Option<NullifierSource>,
+        // Defaults to GracePeriod::ROTATION_DEFAULT, a week
+        #[serde(default)]
+        grace_period: Option<GracePeriod>,
+    },
+    // Same as RegisterVerifyingKey, taking a snarkjs verification_key.json as is
+    RegisterSnarkjsVerifyingKey {
+        circuit_id: String,
+        vkey: SnarkjsVkey,
+        #[serde(default)]
+        nullifier: Option<NullifierSource>,
+        #[serde(default)]
+        grace_period: Option<GracePeriod>,
+    },
+    // Admin only. Registers a key version for one of the circuit templates, taking the snarkjs
+    // verification_key.json its circom source compiles to, and names the circuit's public inputs
+    // after the template. The key must be a BN254 one with the template's public inputs
+    RegisterTemplateKey {
+        circuit_id: String,
+        template: CircuitTemplate,
+        vkey: SnarkjsVkey,
+        #[serde(default)]
+        grace_period: Option<GracePeriod>,
+    },
+    // Admin only. Registers a key version for a PLONK circuit, taking a snarkjs PLONK
+    // verification_key.json as is. A circuit keeps the proof system of its first key.
+    RegisterPlonkVerifyingKey {
+        circuit_id: String,
+        vkey: PlonkVkey,
+        #[serde(default)]
+        nullifier: Option<NullifierSource>,
+        #[serde(default)]
+        grace_period: Option<GracePeriod>,
+    },
+    // Admin only. Makes a deprecated key version the active one again, deprecating the current one
+    // for GracePeriod::ROTATION_DEFAULT
+    ActivateVerifyingKey {
+        circuit_id: String,
+        version: u64,
+    },
+    // Admin only. Without a grace period the key is accepted until revoked. Calling this again on a
+    // deprecated key replaces its grace period
+    DeprecateVerifyingKey {
+        circuit_id: String,
+        version: u64,
+        grace_period: Option<GracePeriod>,
+    },
+    // Admin only
+    RevokeVerifyingKey {
+        circuit_id: String,
+        version: u64,
+    },
+    // Admin only. Names the circuit's public inputs, so VerifyProof can take them by name. There must
+    // be one name per public input of the active key, and later keys must keep that count
+    SetInputNames {
+        circuit_id: String,
+        input_names: InputNames,
+    },
+    // Admin only. Makes VerifyAndExecute available for circuit_id, with the public input at index
+    // holding the hash of the messages to dispatch, as the MsgsHash query computes it
+    SetMsgsHashInput {
+        circuit_id: String,
+        index: usize,
+    },
+    // Same check as QueryMsg::VerifyProof, but a proof is accepted only once per nullifier. Fails
+    // for circuits registered without a nullifier source
+    SubmitProof {
+        circuit_id: String,
+        proof: String,
+        public_inputs: Vec<PublicInput>,
+        #[serde(default)]
+        key_version: Option<u64>,
+    },
+    // Dispatches msgs only if the proof verifies, and fails the transaction otherwise. The messages
+    // are sent by this contract, so they can spend anything it holds. The proof has to commit to
+    // them: the circuit's messages hash input (see SetMsgsHashInput) must equal msgs_hash(msgs).
+    // Its nullifier is then used up as SubmitProof does, so a proof executes once. Circuits using
+    // the proof hash as nullifier are refused
+    VerifyAndExecute {
+        circuit_id: String,
+        proof: String,
+        public_inputs: Vec<PublicInput>,
+        msgs: Vec<CosmosMsg>,
+        #[serde(default)]
+        key_version: Option<u64>,
+    },
+    // Admin only. Sets up a Merkle tree whose roots membership proofs of circuit_id are checked
+    // against. The owner, if any, can publish roots too. Roots are added with UpdateMerkleRoot
+    CreateMerkleTree {
+        tree_id: String,
+        circuit_id: String,
+        // Position of the root in the circuit's public inputs
+        root_index: usize,
+        owner: Option<String>,
+        // How many of the latest roots are accepted, at most MAX_ROOT_HISTORY
+        history_size: u32,
+    },
+    // Admin or tree owner only. Makes root the latest accepted root, dropping the oldest one once
+    // history_size roots are stored
+    UpdateMerkleRoot {
+        tree_id: String,
+        root: PublicInput,
+    },
+    // Sets up an empty Semaphore group, whose members can then signal anonymously. circuit_id must be
+    // a BN254 Groth16 circuit taking [merkle_tree_root, nullifier_hash, signal_hash,
+    // external_nullifier_hash], compiled for depth. Anyone can create a group, admin defaults to the
+    // sender
+    CreateGroup {
+        group_id: Uint256,
+        circuit_id: String,
+        // Between 1 and semaphore::MAX_DEPTH
+        depth: u8,
+        admin: Option<String>,
+        // How many of the latest roots signals are accepted against, at most MAX_ROOT_HISTORY
+        history_size: u32,
+    },
+    // Group admin only. Adds the commitment as the next leaf and makes the new root the latest one
+    AddMember {
+        group_id: Uint256,
+        identity_commitment: PublicInput,
+    },
+    // A group member's anonymous signal, accepted once per nullifier_hash. The proof shows that a
+    // member of the tree with root merkle_tree_root derived nullifier_hash from external_nullifier.
+    // signal and external_nullifier are hashed into the public inputs as Semaphore does
+    Signal {
+        group_id: Uint256,
+        merkle_tree_root: PublicInput,
+        signal: Uint256,
+        nullifier_hash: PublicInput,
+        external_nullifier: Uint256,
+        proof: String,
+    },
+    // Admin only. Hands control over right away and drops any pending proposal
+    UpdateAdmin {
+        admin_address: String,
+    },
+    // Admin only. Nominates the next admin, who takes over with AcceptAdmin. A new proposal replaces
+    // the previous one
+    ProposeAdmin {
+        admin_address: String,
+    },
+    // Only the proposed admin
+    AcceptAdmin {},
+    // Admin only. Leaves the contract without an admin for good, so registered keys can no longer be
+    // added to, rotated or revoked
+    RenounceAdmin {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -21,11 +183,119 @@ pub enum QueryMsg {
         b: u64,
         sum: u64,  
     },
-    // c <- a * b Circuit proof
+    // Proof checked against the key registered for circuit_id, with the circuit's proof system.
+    // Groth16 proofs are either a ProofStr or a snarkjs proof.json, PLONK proofs a snarkjs PLONK
+    // proof.json. Returns VerifyProofResponse, which also reports malformed proofs and inputs;
+    // errors are kept for an unknown circuit_id, a key hash mismatch and named inputs that do not
+    // match the circuit's names
     VerifyProof {
+        circuit_id: String,
         proof: String,
-        expected_value: u64,
-    }
+        // One value per public input, in circuit order
+        #[serde(default)]
+        public_inputs: Vec<PublicInput>,
+        // Checks against this key version only. Otherwise the active key is tried first, then the
+        // deprecated keys still accepted, newest first
+        #[serde(default)]
+        key_version: Option<u64>,
+        // Hex SHA-256 digest, as reported by the Circuit query. Only keys with this digest are tried,
+        // and the query errors if there is none
+        #[serde(default)]
+        expected_vkey_hash: Option<String>,
+        // Instead of public_inputs, every public input keyed by its name, see SetInputNames. The
+        // response lists them in circuit order
+        #[serde(default)]
+        named_inputs: Option<BTreeMap<String, PublicInput>>,
+    },
+    // Several proofs against the same circuit, as (proof, public_inputs) pairs, each accepted by the
+    // key versions VerifyProof would try. The batch check uses the first of them, proofs it rejects
+    // are checked against the others one at a time
+    VerifyProofBatch {
+        circuit_id: String,
+        proofs: Vec<(String, Vec<PublicInput>)>,
+        #[serde(default)]
+        key_version: Option<u64>,
+    },
+    // Registered circuits, ordered by id
+    ListCircuits {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    Circuit {
+        id: String,
+    },
+    // Key versions registered for circuit_id, oldest first
+    ListKeyVersions {
+        circuit_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // Returns CircuitTemplatesResponse
+    CircuitTemplates {},
+    // The public input VerifyAndExecute expects for msgs, returns MsgsHashResponse
+    MsgsHash {
+        msgs: Vec<CosmosMsg>,
+    },
+    // Returns GroupResponse
+    Group {
+        group_id: Uint256,
+    },
+    // Returns ConfigResponse
+    Config {},
+    // Proof that a member of the tree's set satisfies its circuit, checked like VerifyProof once the
+    // public input at the tree's root_index is found among the accepted roots
+    VerifyMembershipProof {
+        tree_id: String,
+        proof: String,
+        public_inputs: Vec<PublicInput>,
+        #[serde(default)]
+        key_version: Option<u64>,
+        #[serde(default)]
+        expected_vkey_hash: Option<String>,
+    },
+    // Returns MerkleTreeResponse
+    MerkleTree {
+        tree_id: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ConfigResponse {
+    // None after the admin renounced
+    pub admin_address: Option<Addr>,
+    pub pending_admin: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MerkleTreeResponse {
+    pub tree_id: String,
+    pub circuit_id: String,
+    pub root_index: usize,
+    pub owner: Option<Addr>,
+    pub history_size: u32,
+    // Accepted roots, newest first
+    pub roots: Vec<PublicInput>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct GroupResponse {
+    pub group_id: Uint256,
+    pub circuit_id: String,
+    pub admin: Addr,
+    pub depth: u8,
+    pub num_members: u64,
+    pub history_size: u32,
+    // Accepted roots, newest first
+    pub roots: Vec<PublicInput>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MsgsHashResponse {
+    pub msgs_hash: PublicInput,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -38,9 +308,106 @@ pub struct VerifyTruthResponse {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct VerifyProofResponse {
-    pub result: String,
+    pub valid: bool,
+    pub circuit_id: String,
+    // The key version the proof verified against, or the first one tried when it did not verify
+    pub key_version: u64,
+    // The public inputs the proof was checked against, as submitted
+    pub public_inputs: Vec<PublicInput>,
+    // Set exactly when valid is false
+    pub failure: Option<VerificationFailure>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub enum MigrateMsg {}
+pub enum VerificationFailure {
+    // The proof or one of the public inputs could not be decoded
+    ParseError { msg: String },
+    // Everything decoded, but the pairing check did not hold
+    PairingFailure,
+    // Wrong number of public inputs for the circuit
+    InputMismatch { expected: usize, actual: usize },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct VerifyProofBatchResponse {
+    // True only if every proof in the batch verified
+    pub all_valid: bool,
+    // One entry per submitted proof, in order. Malformed proofs and proofs with the wrong number of
+    // public inputs are false
+    pub results: Vec<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CircuitsResponse {
+    pub circuits: Vec<CircuitResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CircuitResponse {
+    pub id: String,
+    pub active_version: Option<u64>,
+    pub proof_system: ProofSystem,
+    // The rest describes this key version: the active one, or the latest when none is active
+    pub key_version: u64,
+    pub status: KeyStatus,
+    pub num_public_inputs: u32,
+    pub curve: Curve,
+    // Hex encoded SHA-256 of the verifying key
+    pub key_hash: String,
+    pub registered_height: u64,
+    pub registrant: Addr,
+    // Empty until SetInputNames
+    pub input_names: Vec<String>,
+    pub template: Option<CircuitTemplate>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CircuitTemplatesResponse {
+    pub templates: Vec<CircuitTemplateInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CircuitTemplateInfo {
+    pub template: CircuitTemplate,
+    // In circuit order
+    pub input_names: Vec<String>,
+    // What RegisterTemplateKey expects of the template's keys
+    pub curve: Curve,
+    pub proof_system: ProofSystem,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct KeyVersionsResponse {
+    pub circuit_id: String,
+    pub active_version: Option<u64>,
+    pub versions: Vec<KeyVersionInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct KeyVersionInfo {
+    pub version: u64,
+    pub status: KeyStatus,
+    pub key_hash: String,
+    // Whether proofs are accepted against this version at the current block
+    pub usable: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {
+    // "crates.io:verifier-contract" to confirm a migration from 0.1.x, whose contract name other
+    // contracts stored too
+    #[serde(default)]
+    pub legacy_contract: Option<String>,
+    // When migrating from 0.1.x, where the contract verified against one hardcoded multiplier key,
+    // registers that key as the first version of this circuit so the proofs made for it still verify
+    pub legacy_circuit_id: Option<String>,
+}
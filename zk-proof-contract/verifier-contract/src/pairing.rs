@@ -0,0 +1,77 @@
+use bls12_381::{multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, Gt};
+use cosmwasm_std::{Api, StdError, VerificationError};
+
+use crate::error::ContractError;
+
+// Groth16 over BLS12-381 needs a sum of G1 points and a multi-pairing check. Where those run is
+// up to the backend: RustBackend computes them inside the contract with the bls12_381 crate,
+// NativeBackend hands them to the BLS12-381 host functions of the CosmWasm 2.1 Api.
+// The native-bls feature picks the backend the contract verifies with, see default_backend.
+pub trait PairingBackend {
+    // Sum of the points, which must not be empty
+    fn aggregate_g1(&self, points: &[G1Affine]) -> Result<G1Affine, ContractError>;
+
+    // Whether the product of e(p, q) over all the pairs is the identity
+    fn pairing_check(&self, pairs: &[(G1Affine, G2Affine)]) -> Result<bool, ContractError>;
+}
+
+pub struct RustBackend;
+
+impl PairingBackend for RustBackend {
+    fn aggregate_g1(&self, points: &[G1Affine]) -> Result<G1Affine, ContractError> {
+        let sum = points.iter().fold(G1Projective::identity(), |acc, point| acc + point);
+        Ok(sum.into())
+    }
+
+    fn pairing_check(&self, pairs: &[(G1Affine, G2Affine)]) -> Result<bool, ContractError> {
+        let prepared: Vec<(G1Affine, G2Prepared)> = pairs.iter().map(|(p, q)| (*p, G2Prepared::from(*q))).collect();
+        let terms: Vec<(&G1Affine, &G2Prepared)> = prepared.iter().map(|(p, q)| (p, q)).collect();
+        Ok(multi_miller_loop(&terms).final_exponentiation() == Gt::identity())
+    }
+}
+
+// Points go to the Api in the compressed zcash encoding, several points back to back
+pub struct NativeBackend<'a> {
+    api: &'a dyn Api,
+}
+
+impl<'a> NativeBackend<'a> {
+    pub fn new(api: &'a dyn Api) -> Self {
+        NativeBackend { api }
+    }
+}
+
+impl PairingBackend for NativeBackend<'_> {
+    fn aggregate_g1(&self, points: &[G1Affine]) -> Result<G1Affine, ContractError> {
+        let g1s: Vec<u8> = points.iter().flat_map(|point| point.to_compressed()).collect();
+        let sum = self.api.bls12_381_aggregate_g1(&g1s).map_err(StdError::from)?;
+        // The host only returns points it has validated
+        Option::from(G1Affine::from_compressed_unchecked(&sum))
+            .ok_or_else(|| StdError::from(VerificationError::GenericErr).into())
+    }
+
+    // The host compares against e(r, s), so the last pair moves to the right hand side negated
+    fn pairing_check(&self, pairs: &[(G1Affine, G2Affine)]) -> Result<bool, ContractError> {
+        let ((last_p, last_q), rest) = match pairs.split_last() {
+            Some(split) => split,
+            None => return Ok(true),
+        };
+        let ps: Vec<u8> = rest.iter().flat_map(|(p, _)| p.to_compressed()).collect();
+        let qs: Vec<u8> = rest.iter().flat_map(|(_, q)| q.to_compressed()).collect();
+        let r = (-last_p).to_compressed();
+        let s = last_q.to_compressed();
+        Ok(self.api.bls12_381_pairing_equality(&ps, &qs, &r, &s).map_err(StdError::from)?)
+    }
+}
+
+// The api is only used with native-bls. In Wasm it calls the chain's host functions, in unit tests
+// cosmwasm-std's MockApi computes the same pairings natively
+#[cfg(not(feature = "native-bls"))]
+pub fn default_backend(_api: &dyn Api) -> RustBackend {
+    RustBackend
+}
+
+#[cfg(feature = "native-bls")]
+pub fn default_backend(api: &dyn Api) -> NativeBackend<'_> {
+    NativeBackend::new(api)
+}
@@ -0,0 +1,475 @@
+use ark_bn254::{Bn254, Fq, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::pairing::Pairing;
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::{BigInteger, Field, One, PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+use crate::bn254;
+use crate::error::ContractError;
+use crate::snarkjs::{g1_bytes, g2_bytes};
+use crate::state::{decimal_to_le_bytes, Curve};
+
+// PLONK with KZG commitments over BN254, as snarkjs 0.7 and later produce it. Keys and proofs are
+// taken straight from its verification_key.json and proof.json, and proofs are checked the way
+// snarkjs' own verifier does, keccak256 Fiat-Shamir transcript included.
+
+const PROTOCOL: &str = "plonk";
+const CURVE: &str = "bn128";
+
+// The BN254 scalar field has roots of unity of order up to 2^28
+const MAX_POWER: u32 = 28;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PlonkVkey {
+    pub protocol: String,
+    pub curve: String,
+    #[serde(rename = "nPublic")]
+    pub n_public: usize,
+    // The circuit has 2^power rows
+    pub power: u32,
+    // Coset shifts of the permutation argument
+    pub k1: String,
+    pub k2: String,
+    // Commitments to the selector and permutation polynomials
+    #[serde(rename = "Qm")]
+    pub qm: Vec<String>,
+    #[serde(rename = "Ql")]
+    pub ql: Vec<String>,
+    #[serde(rename = "Qr")]
+    pub qr: Vec<String>,
+    #[serde(rename = "Qo")]
+    pub qo: Vec<String>,
+    #[serde(rename = "Qc")]
+    pub qc: Vec<String>,
+    #[serde(rename = "S1")]
+    pub s1: Vec<String>,
+    #[serde(rename = "S2")]
+    pub s2: Vec<String>,
+    #[serde(rename = "S3")]
+    pub s3: Vec<String>,
+    // [tau]_2 from the powers of tau ceremony
+    #[serde(rename = "X_2")]
+    pub x_2: Vec<Vec<String>>,
+    // Generator of the 2^power roots of unity
+    pub w: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PlonkProof {
+    pub protocol: String,
+    pub curve: String,
+    #[serde(rename = "A")]
+    pub a: Vec<String>,
+    #[serde(rename = "B")]
+    pub b: Vec<String>,
+    #[serde(rename = "C")]
+    pub c: Vec<String>,
+    #[serde(rename = "Z")]
+    pub z: Vec<String>,
+    #[serde(rename = "T1")]
+    pub t1: Vec<String>,
+    #[serde(rename = "T2")]
+    pub t2: Vec<String>,
+    #[serde(rename = "T3")]
+    pub t3: Vec<String>,
+    #[serde(rename = "Wxi")]
+    pub wxi: Vec<String>,
+    #[serde(rename = "Wxiw")]
+    pub wxiw: Vec<String>,
+    pub eval_a: String,
+    pub eval_b: String,
+    pub eval_c: String,
+    pub eval_s1: String,
+    pub eval_s2: String,
+    pub eval_zw: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifyingKey {
+    pub n_public: usize,
+    pub power: u32,
+    pub k1: Fr,
+    pub k2: Fr,
+    pub qm: G1Affine,
+    pub ql: G1Affine,
+    pub qr: G1Affine,
+    pub qo: G1Affine,
+    pub qc: G1Affine,
+    pub s1: G1Affine,
+    pub s2: G1Affine,
+    pub s3: G1Affine,
+    pub x_2: G2Affine,
+    pub w: Fr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Proof {
+    pub a: G1Affine,
+    pub b: G1Affine,
+    pub c: G1Affine,
+    pub z: G1Affine,
+    pub t1: G1Affine,
+    pub t2: G1Affine,
+    pub t3: G1Affine,
+    pub wxi: G1Affine,
+    pub wxiw: G1Affine,
+    pub eval_a: Fr,
+    pub eval_b: Fr,
+    pub eval_c: Fr,
+    pub eval_s1: Fr,
+    pub eval_s2: Fr,
+    pub eval_zw: Fr,
+}
+
+pub fn parse_verification_key(vk: &PlonkVkey) -> Result<VerifyingKey, ContractError> {
+    check_header(&vk.protocol, &vk.curve)?;
+    if vk.power == 0 || vk.power > MAX_POWER || vk.n_public >= 1 << vk.power {
+        return Err(ContractError::InvalidVerifyingKey {});
+    }
+
+    let w = parse_fr("w", &vk.w)?;
+    // w has to generate exactly the 2^power roots of unity
+    let half_order = w.pow([1u64 << (vk.power - 1)]);
+    if half_order.square() != Fr::one() || half_order == Fr::one() {
+        return Err(ContractError::InvalidVerifyingKey {});
+    }
+
+    Ok(VerifyingKey {
+        n_public: vk.n_public,
+        power: vk.power,
+        k1: parse_fr("k1", &vk.k1)?,
+        k2: parse_fr("k2", &vk.k2)?,
+        qm: parse_g1("Qm", &vk.qm)?,
+        ql: parse_g1("Ql", &vk.ql)?,
+        qr: parse_g1("Qr", &vk.qr)?,
+        qo: parse_g1("Qo", &vk.qo)?,
+        qc: parse_g1("Qc", &vk.qc)?,
+        s1: parse_g1("S1", &vk.s1)?,
+        s2: parse_g1("S2", &vk.s2)?,
+        s3: parse_g1("S3", &vk.s3)?,
+        x_2: bn254::parse_g2("X_2", &g2_bytes("X_2", &vk.x_2, Curve::Bn254)?)?,
+        w,
+    })
+}
+
+pub fn decode_proof(proof: &str) -> Result<Proof, ContractError> {
+    let proof: PlonkProof =
+        serde_json::from_str(proof).map_err(|err| ContractError::InvalidProofJson { msg: err.to_string() })?;
+    parse_proof(&proof)
+}
+
+pub fn parse_proof(proof: &PlonkProof) -> Result<Proof, ContractError> {
+    check_header(&proof.protocol, &proof.curve)?;
+
+    Ok(Proof {
+        a: parse_g1("A", &proof.a)?,
+        b: parse_g1("B", &proof.b)?,
+        c: parse_g1("C", &proof.c)?,
+        z: parse_g1("Z", &proof.z)?,
+        t1: parse_g1("T1", &proof.t1)?,
+        t2: parse_g1("T2", &proof.t2)?,
+        t3: parse_g1("T3", &proof.t3)?,
+        wxi: parse_g1("Wxi", &proof.wxi)?,
+        wxiw: parse_g1("Wxiw", &proof.wxiw)?,
+        eval_a: parse_fr("eval_a", &proof.eval_a)?,
+        eval_b: parse_fr("eval_b", &proof.eval_b)?,
+        eval_c: parse_fr("eval_c", &proof.eval_c)?,
+        eval_s1: parse_fr("eval_s1", &proof.eval_s1)?,
+        eval_s2: parse_fr("eval_s2", &proof.eval_s2)?,
+        eval_zw: parse_fr("eval_zw", &proof.eval_zw)?,
+    })
+}
+
+fn check_header(protocol: &str, curve: &str) -> Result<(), ContractError> {
+    if protocol != PROTOCOL {
+        return Err(ContractError::UnsupportedProtocol { protocol: protocol.to_string() });
+    }
+    if curve != CURVE {
+        return Err(ContractError::UnsupportedCurve { curve: curve.to_string() });
+    }
+    Ok(())
+}
+
+fn parse_g1(field: &str, coords: &[String]) -> Result<G1Affine, ContractError> {
+    bn254::parse_g1(field, &g1_bytes(field, coords, Curve::Bn254)?)
+}
+
+fn parse_fr(field: &str, digits: &str) -> Result<Fr, ContractError> {
+    decimal_to_le_bytes(digits)
+        .and_then(bn254::fr_from_le_bytes)
+        .ok_or_else(|| ContractError::InvalidScalar { field: field.to_string() })
+}
+
+impl VerifyingKey {
+    // SHA-256 over "plonk_bn254", nPublic and power as 8 and 4 big-endian bytes, k1, k2 and w, then
+    // every commitment and X_2 in the layout of bn254.rs. Scalars are 32 bytes, big-endian.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(b"plonk_bn254");
+        hasher.update((self.n_public as u64).to_be_bytes());
+        hasher.update(self.power.to_be_bytes());
+        for scalar in [&self.k1, &self.k2, &self.w] {
+            hasher.update(fr_be_bytes(scalar));
+        }
+        for point in self.commitments() {
+            hasher.update(g1_be_bytes(point));
+        }
+        hasher.update(g2_be_bytes(&self.x_2));
+        hasher.finalize().into()
+    }
+
+    // nPublic and power as 8 and 4 little-endian bytes, then the uncompressed arkworks encoding of
+    // k1, k2, w, the commitments and X_2
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ContractError> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.n_public as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.power.to_le_bytes());
+        let invalid = |_| ContractError::InvalidVerifyingKey {};
+        for scalar in [&self.k1, &self.k2, &self.w] {
+            scalar.serialize_uncompressed(&mut bytes).map_err(invalid)?;
+        }
+        for point in self.commitments() {
+            point.serialize_uncompressed(&mut bytes).map_err(invalid)?;
+        }
+        self.x_2.serialize_uncompressed(&mut bytes).map_err(invalid)?;
+        Ok(bytes)
+    }
+
+    // The key was checked when it was registered, so it is read back without validation
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ContractError> {
+        if bytes.len() < 12 {
+            return Err(ContractError::InvalidVerifyingKey {});
+        }
+        let n_public = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize;
+        let power = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        let mut reader = &bytes[12..];
+        let mut scalar = || Fr::deserialize_with_mode(&mut reader, Compress::No, Validate::No);
+        let (k1, k2, w) = match (scalar(), scalar(), scalar()) {
+            (Ok(k1), Ok(k2), Ok(w)) => (k1, k2, w),
+            _ => return Err(ContractError::InvalidVerifyingKey {}),
+        };
+        let mut points = Vec::new();
+        for _ in 0..8 {
+            let point = G1Affine::deserialize_with_mode(&mut reader, Compress::No, Validate::No)
+                .map_err(|_| ContractError::InvalidVerifyingKey {})?;
+            points.push(point);
+        }
+        let x_2 = G2Affine::deserialize_with_mode(&mut reader, Compress::No, Validate::No)
+            .map_err(|_| ContractError::InvalidVerifyingKey {})?;
+
+        Ok(VerifyingKey {
+            n_public,
+            power,
+            k1,
+            k2,
+            qm: points[0],
+            ql: points[1],
+            qr: points[2],
+            qo: points[3],
+            qc: points[4],
+            s1: points[5],
+            s2: points[6],
+            s3: points[7],
+            x_2,
+            w,
+        })
+    }
+
+    fn commitments(&self) -> [&G1Affine; 8] {
+        [&self.qm, &self.ql, &self.qr, &self.qo, &self.qc, &self.s1, &self.s2, &self.s3]
+    }
+}
+
+impl Proof {
+    // Every commitment, then every evaluation, in the layout of bn254.rs
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for point in [&self.a, &self.b, &self.c, &self.z, &self.t1, &self.t2, &self.t3, &self.wxi, &self.wxiw] {
+            bytes.extend(g1_be_bytes(point));
+        }
+        for scalar in [&self.eval_a, &self.eval_b, &self.eval_c, &self.eval_s1, &self.eval_s2, &self.eval_zw] {
+            bytes.extend(fr_be_bytes(scalar));
+        }
+        bytes
+    }
+}
+
+// snarkjs' Keccak256Transcript. Points go in as x || y and scalars as 32 bytes, big-endian, and
+// every challenge is the keccak256 of everything added since the last one, reduced modulo the
+// scalar field order.
+#[derive(Default)]
+pub struct Transcript {
+    data: Vec<u8>,
+}
+
+impl Transcript {
+    pub fn add_point(&mut self, point: &G1Affine) {
+        let mut bytes = g1_be_bytes(point);
+        // The point at infinity is all zero apart from this flag
+        if point.is_zero() {
+            bytes[0] |= 0x40;
+        }
+        self.data.extend(bytes);
+    }
+
+    pub fn add_scalar(&mut self, scalar: &Fr) {
+        self.data.extend(fr_be_bytes(scalar));
+    }
+
+    pub fn challenge(&mut self) -> Fr {
+        let digest = Keccak256::digest(&self.data);
+        self.data.clear();
+        Fr::from_be_bytes_mod_order(&digest)
+    }
+}
+
+pub struct Challenges {
+    pub beta: Fr,
+    pub gamma: Fr,
+    pub alpha: Fr,
+    pub xi: Fr,
+    // v[0] is unused, v[i] is the ith power of the first challenge
+    pub v: [Fr; 6],
+    pub u: Fr,
+}
+
+pub fn challenges(vk: &VerifyingKey, proof: &Proof, public_inputs: &[Fr]) -> Challenges {
+    let mut transcript = Transcript::default();
+    for point in vk.commitments() {
+        transcript.add_point(point);
+    }
+    for input in public_inputs.iter() {
+        transcript.add_scalar(input);
+    }
+    transcript.add_point(&proof.a);
+    transcript.add_point(&proof.b);
+    transcript.add_point(&proof.c);
+    let beta = transcript.challenge();
+
+    transcript.add_scalar(&beta);
+    let gamma = transcript.challenge();
+
+    transcript.add_scalar(&beta);
+    transcript.add_scalar(&gamma);
+    transcript.add_point(&proof.z);
+    let alpha = transcript.challenge();
+
+    transcript.add_scalar(&alpha);
+    transcript.add_point(&proof.t1);
+    transcript.add_point(&proof.t2);
+    transcript.add_point(&proof.t3);
+    let xi = transcript.challenge();
+
+    transcript.add_scalar(&xi);
+    for eval in [&proof.eval_a, &proof.eval_b, &proof.eval_c, &proof.eval_s1, &proof.eval_s2, &proof.eval_zw] {
+        transcript.add_scalar(eval);
+    }
+    let mut v = [Fr::zero(); 6];
+    v[1] = transcript.challenge();
+    for i in 2..6 {
+        v[i] = v[i - 1] * v[1];
+    }
+
+    transcript.add_point(&proof.wxi);
+    transcript.add_point(&proof.wxiw);
+    let u = transcript.challenge();
+
+    Challenges { beta, gamma, alpha, xi, v, u }
+}
+
+// Follows snarkjs' plonk_verify: the linearization commitment D and the batched opening F and E
+// reduce the whole check to e(-(Wxi + u * Wxiw), X_2) * e(xi * Wxi + u * xi * w * Wxiw + F - E, G2) = 1
+pub fn verify_proof(vk: &VerifyingKey, proof: &Proof, public_inputs: &[Fr]) -> bool {
+    if public_inputs.len() != vk.n_public {
+        return false;
+    }
+    let ch = challenges(vk, proof, public_inputs);
+
+    // xi^n, the vanishing polynomial at xi and the Lagrange polynomials of the public input rows
+    let xin = ch.xi.pow([1u64 << vk.power]);
+    let zh = xin - Fr::one();
+    let n = Fr::from(1u64 << vk.power);
+    let mut lagrange = Vec::new();
+    let mut w = Fr::one();
+    for _ in 0..vk.n_public.max(1) {
+        // xi is one of the roots of unity only with negligible probability
+        let denominator = match (n * (ch.xi - w)).inverse() {
+            Some(inverse) => inverse,
+            None => return false,
+        };
+        lagrange.push(w * zh * denominator);
+        w *= vk.w;
+    }
+    let l1 = lagrange[0];
+    let pi = public_inputs
+        .iter()
+        .zip(lagrange.iter())
+        .fold(Fr::zero(), |pi, (input, l)| pi - *input * l);
+
+    let alpha2 = ch.alpha.square();
+    let e3a = proof.eval_a + ch.beta * proof.eval_s1 + ch.gamma;
+    let e3b = proof.eval_b + ch.beta * proof.eval_s2 + ch.gamma;
+    let e3c = proof.eval_c + ch.gamma;
+    let r0 = pi - l1 * alpha2 - e3a * e3b * e3c * proof.eval_zw * ch.alpha;
+
+    let d1 = vk.qm * (proof.eval_a * proof.eval_b)
+        + vk.ql * proof.eval_a
+        + vk.qr * proof.eval_b
+        + vk.qo * proof.eval_c
+        + vk.qc;
+    let betaxi = ch.beta * ch.xi;
+    let d2a = (proof.eval_a + betaxi + ch.gamma)
+        * (proof.eval_b + betaxi * vk.k1 + ch.gamma)
+        * (proof.eval_c + betaxi * vk.k2 + ch.gamma)
+        * ch.alpha;
+    let d2 = proof.z * (d2a + l1 * alpha2 + ch.u);
+    let d3 = vk.s3 * (e3a * e3b * ch.alpha * ch.beta * proof.eval_zw);
+    let d4 = (G1Projective::from(proof.t1) + proof.t2 * xin + proof.t3 * xin.square()) * zh;
+    let d = d1 + d2 - d3 - d4;
+
+    let f = d
+        + proof.a * ch.v[1]
+        + proof.b * ch.v[2]
+        + proof.c * ch.v[3]
+        + vk.s1 * ch.v[4]
+        + vk.s2 * ch.v[5];
+    let e = G1Affine::generator()
+        * (-r0
+            + ch.v[1] * proof.eval_a
+            + ch.v[2] * proof.eval_b
+            + ch.v[3] * proof.eval_c
+            + ch.v[4] * proof.eval_s1
+            + ch.v[5] * proof.eval_s2
+            + ch.u * proof.eval_zw);
+
+    let a1 = G1Projective::from(proof.wxi) + proof.wxiw * ch.u;
+    let b1 = proof.wxi * ch.xi + proof.wxiw * (ch.u * ch.xi * vk.w) + f - e;
+    Bn254::multi_pairing([(-a1).into_affine(), b1.into_affine()], [vk.x_2, G2Affine::generator()]).is_zero()
+}
+
+fn fr_be_bytes(scalar: &Fr) -> Vec<u8> {
+    scalar.into_bigint().to_bytes_be()
+}
+
+fn fq_be_bytes(coord: &Fq) -> Vec<u8> {
+    coord.into_bigint().to_bytes_be()
+}
+
+// x || y, or all zero for the point at infinity
+fn g1_be_bytes(point: &G1Affine) -> Vec<u8> {
+    match point.xy() {
+        Some((x, y)) => [fq_be_bytes(x), fq_be_bytes(y)].concat(),
+        None => vec![0u8; 64],
+    }
+}
+
+fn g2_be_bytes(point: &G2Affine) -> Vec<u8> {
+    match point.xy() {
+        Some((x, y)) => [fq_be_bytes(&x.c1), fq_be_bytes(&x.c0), fq_be_bytes(&y.c1), fq_be_bytes(&y.c0)].concat(),
+        None => vec![0u8; 128],
+    }
+}
@@ -0,0 +1,74 @@
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use cosmwasm_std::{Binary, Uint256};
+use light_poseidon::{Poseidon, PoseidonHasher};
+use sha3::{Digest, Keccak256};
+
+use crate::bn254::fr_from_le_bytes;
+use crate::error::ContractError;
+use crate::state::Group;
+
+// Semaphore (v3) groups: identity commitments are the leaves of an incremental binary Merkle tree
+// hashed with circomlib's Poseidon over BN254, the way the Semaphore circuits recompute the root.
+// Field elements are stored as 32 little endian bytes, like Merkle roots elsewhere in the contract.
+
+// Deepest tree the Semaphore circuits are compiled for
+pub const MAX_DEPTH: u8 = 32;
+
+// Semaphore's hash for signals, external nullifiers and group ids: keccak256 of the 32 byte
+// big-endian value, shifted right by 8 bits so the result is below the field modulus
+pub fn hash_to_field(value: Uint256) -> Fr {
+    let digest = Keccak256::digest(value.to_be_bytes());
+    Fr::from_be_bytes_mod_order(&digest[..31])
+}
+
+pub fn poseidon2(hasher: &mut Poseidon<Fr>, left: Fr, right: Fr) -> Fr {
+    // Only fails for an input count the hasher was not built for
+    hasher.hash(&[left, right]).expect("two inputs")
+}
+
+pub fn hasher() -> Poseidon<Fr> {
+    Poseidon::<Fr>::new_circom(2).expect("circom parameters for two inputs")
+}
+
+pub fn fr_to_binary(value: Fr) -> Binary {
+    Binary::new(value.into_bigint().to_bytes_le())
+}
+
+// Values are only ever written by this module, so they are canonical
+fn binary_to_fr(value: &Binary) -> Result<Fr, ContractError> {
+    let bytes = value.as_slice().try_into().map_err(|_| ContractError::InvalidMerkleRoot {})?;
+    fr_from_le_bytes(bytes).ok_or(ContractError::InvalidMerkleRoot {})
+}
+
+// The empty tree. Every leaf is the group's zero value, keccak of the group id as Semaphore's
+// contracts derive it, so the root of an empty group differs between groups
+pub fn new_tree(group_id: Uint256, depth: u8) -> (Vec<Binary>, Binary) {
+    let mut hasher = hasher();
+    let mut zero = hash_to_field(group_id);
+    let mut zeroes = Vec::new();
+    for _ in 0..depth {
+        zeroes.push(fr_to_binary(zero));
+        zero = poseidon2(&mut hasher, zero, zero);
+    }
+    (zeroes, fr_to_binary(zero))
+}
+
+// Appends leaf at the next free index and returns the new root. Only the path to the root is
+// rehashed: filled_subtrees holds, per level, the last left node written there
+pub fn insert(group: &mut Group, leaf: Fr) -> Result<Binary, ContractError> {
+    let mut hasher = hasher();
+    let mut index = group.num_members;
+    let mut node = leaf;
+    for level in 0..group.depth as usize {
+        if index & 1 == 0 {
+            group.filled_subtrees[level] = fr_to_binary(node);
+            node = poseidon2(&mut hasher, node, binary_to_fr(&group.zeroes[level])?);
+        } else {
+            node = poseidon2(&mut hasher, binary_to_fr(&group.filled_subtrees[level])?, node);
+        }
+        index >>= 1;
+    }
+    group.num_members += 1;
+    Ok(fr_to_binary(node))
+}
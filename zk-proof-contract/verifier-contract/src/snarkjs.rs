@@ -0,0 +1,154 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::Binary;
+
+use crate::error::ContractError;
+use crate::state::{decimal_to_le_bytes, Curve, PointEncoding, ProofStr, VkeyStr};
+
+// snarkjs writes proof.json and verification_key.json with decimal string coordinates.
+// G1 points are [x, y, z] and G2 points [[x_c0, x_c1], [y_c0, y_c1], [z_c0, z_c1]],
+// always normalized so that z is one, or zero for the point at infinity.
+
+const PROTOCOL: &str = "groth16";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SnarkjsVkey {
+    pub protocol: String,
+    pub curve: String,
+    #[serde(rename = "nPublic")]
+    pub n_public: usize,
+    pub vk_alpha_1: Vec<String>,
+    pub vk_beta_2: Vec<Vec<String>>,
+    pub vk_gamma_2: Vec<Vec<String>>,
+    pub vk_delta_2: Vec<Vec<String>>,
+    #[serde(rename = "IC")]
+    pub ic: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SnarkjsProof {
+    pub protocol: String,
+    pub curve: String,
+    pub pi_a: Vec<String>,
+    pub pi_b: Vec<Vec<String>>,
+    pub pi_c: Vec<String>,
+}
+
+impl SnarkjsVkey {
+    pub fn to_vkey_str(&self) -> Result<VkeyStr, ContractError> {
+        check_protocol(&self.protocol)?;
+        let curve = parse_curve(&self.curve)?;
+        if self.ic.len() != self.n_public + 1 {
+            return Err(ContractError::InvalidVerifyingKey {});
+        }
+
+        let mut ic = Vec::new();
+        for (i, point) in self.ic.iter().enumerate() {
+            ic.push(g1_bytes(&format!("IC[{}]", i), point, curve)?);
+        }
+
+        Ok(VkeyStr {
+            curve,
+            encoding: PointEncoding::Uncompressed,
+            alpha_1: g1_bytes("vk_alpha_1", &self.vk_alpha_1, curve)?,
+            beta_2: g2_bytes("vk_beta_2", &self.vk_beta_2, curve)?,
+            gamma_2: g2_bytes("vk_gamma_2", &self.vk_gamma_2, curve)?,
+            delta_2: g2_bytes("vk_delta_2", &self.vk_delta_2, curve)?,
+            ic,
+        })
+    }
+}
+
+impl SnarkjsProof {
+    // The proof has to be on the same curve as the key it is checked against
+    pub fn to_proof_str(&self, curve: Curve) -> Result<ProofStr, ContractError> {
+        check_protocol(&self.protocol)?;
+        if parse_curve(&self.curve)? != curve {
+            return Err(ContractError::UnsupportedCurve { curve: self.curve.clone() });
+        }
+
+        Ok(ProofStr {
+            encoding: PointEncoding::Uncompressed,
+            pi_a: g1_bytes("pi_a", &self.pi_a, curve)?,
+            pi_b: g2_bytes("pi_b", &self.pi_b, curve)?,
+            pi_c: g1_bytes("pi_c", &self.pi_c, curve)?,
+        })
+    }
+}
+
+fn check_protocol(protocol: &str) -> Result<(), ContractError> {
+    if protocol != PROTOCOL {
+        return Err(ContractError::UnsupportedProtocol { protocol: protocol.to_string() });
+    }
+    Ok(())
+}
+
+fn parse_curve(curve: &str) -> Result<Curve, ContractError> {
+    match curve {
+        "bls12381" => Ok(Curve::Bls12_381),
+        "bn128" => Ok(Curve::Bn254),
+        _ => Err(ContractError::UnsupportedCurve { curve: curve.to_string() }),
+    }
+}
+
+// Converts to the uncompressed encoding: x || y, both big-endian
+pub(crate) fn g1_bytes(field: &str, coords: &[String], curve: Curve) -> Result<Binary, ContractError> {
+    let invalid = || ContractError::InvalidCoordinates { field: field.to_string() };
+    if coords.len() != 3 {
+        return Err(invalid());
+    }
+
+    match coords[2].as_str() {
+        "0" => Ok(Binary::new(infinity(curve, 2))),
+        "1" => {
+            let x = fp_bytes(&coords[0], curve).ok_or_else(invalid)?;
+            let y = fp_bytes(&coords[1], curve).ok_or_else(invalid)?;
+            Ok(Binary::new([x, y].concat()))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+// Converts to the uncompressed encoding, which puts c1 before c0 in each coordinate
+pub(crate) fn g2_bytes(field: &str, coords: &[Vec<String>], curve: Curve) -> Result<Binary, ContractError> {
+    let invalid = || ContractError::InvalidCoordinates { field: field.to_string() };
+    if coords.len() != 3 || coords.iter().any(|coord| coord.len() != 2) {
+        return Err(invalid());
+    }
+
+    match (coords[2][0].as_str(), coords[2][1].as_str()) {
+        ("0", "0") => Ok(Binary::new(infinity(curve, 4))),
+        ("1", "0") => {
+            let mut bytes = Vec::new();
+            for coord in [&coords[0][1], &coords[0][0], &coords[1][1], &coords[1][0]] {
+                bytes.extend(fp_bytes(coord, curve).ok_or_else(invalid)?);
+            }
+            Ok(Binary::new(bytes))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+// Point at infinity spanning num_coords base field elements
+fn infinity(curve: Curve, num_coords: usize) -> Vec<u8> {
+    match curve {
+        Curve::Bls12_381 => {
+            let mut bytes = vec![0u8; num_coords * 48];
+            // Infinity flag
+            bytes[0] = 0x40;
+            bytes
+        }
+        Curve::Bn254 => vec![0u8; num_coords * 32],
+    }
+}
+
+// Values above the base field modulus are left for point decoding to reject
+fn fp_bytes(digits: &str, curve: Curve) -> Option<Vec<u8>> {
+    let mut bytes = match curve {
+        Curve::Bls12_381 => decimal_to_le_bytes::<48>(digits)?.to_vec(),
+        Curve::Bn254 => decimal_to_le_bytes::<32>(digits)?.to_vec(),
+    };
+    bytes.reverse();
+    Some(bytes)
+}
@@ -1,89 +1,399 @@
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Binary, BlockInfo, CosmosMsg, StdResult, Timestamp, to_json_vec};
+use cw_storage_plus::{Item, Map};
 
-use bellman::groth16::{ Proof, VerifyingKey, PreparedVerifyingKey };
-use bellman::groth16::prepare_verifying_key;
-use bls12_381::{ G1Affine, G2Affine, Bls12, Scalar };
+use bellman::groth16::{ Proof, VerifyingKey };
+use bls12_381::{ G1Affine, G1Projective, G2Affine, Bls12, Scalar };
+use ff::{Field, PrimeField};
+use rand_chacha::ChaCha20Rng;
+use rand_chacha::rand_core::SeedableRng;
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+use crate::pairing::PairingBackend;
+use crate::plonk::PlonkVkey;
+use crate::snarkjs::SnarkjsProof;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
-    pub admin_address: Addr,
+    // Controls key registration and the key lifecycle. None once the admin renounced, after which
+    // nobody can register or change keys. Configs saved before renouncing existed hold a plain address
+    pub admin_address: Option<Addr>,
+    // Address ProposeAdmin nominated, which becomes the admin once it sends AcceptAdmin
+    #[serde(default)]
+    pub pending_admin: Option<Addr>,
 }
 
 pub const CONFIG: Item<Config> = Item::new("state");
 
 // ZK
 
-#[derive(Serialize, Deserialize)]
-pub struct VkeyStr {
-    pub alpha_1: Vec<u8>,
-    pub beta_2: Vec<u8>,
-    pub gamma_2: Vec<u8>,
-    pub delta_2: Vec<u8>,
-    pub ic: Vec<Vec<u8>>,
+// Per circuit bookkeeping, keyed by circuit id
+pub const CIRCUITS: Map<&str, CircuitInfo> = Map::new("circuits");
+
+// Every verifying key registered by the admin, keyed by circuit id and version
+pub const VERIFYING_KEYS: Map<(&str, u64), VkeyStr> = Map::new("verifying_keys");
+
+// Verifying keys of PLONK circuits, keyed by circuit id and version. VERIFYING_KEYS only holds
+// Groth16 keys.
+pub const PLONK_KEYS: Map<(&str, u64), PlonkVkey> = Map::new("plonk_keys");
+
+// Verifying keys in the form the pairing check consumes, written at registration so queries skip
+// point decompression, subgroup checks and key preparation. Keyed by circuit id and version.
+pub const PREPARED_KEYS: Map<(&str, u64), Binary> = Map::new("prepared_keys");
+
+// Lifecycle state of every key version, keyed by circuit id and version
+pub const KEY_STATUSES: Map<(&str, u64), KeyStatus> = Map::new("key_statuses");
+
+// What was recorded when each key version was registered, keyed by circuit id and version
+pub const KEY_REGISTRATIONS: Map<(&str, u64), KeyRegistration> = Map::new("key_registrations");
+
+// Nullifiers already accepted by SubmitProof, keyed by circuit id and nullifier. The value is the
+// height of the block that recorded them.
+pub const NULLIFIERS: Map<(&str, &[u8]), u64> = Map::new("nullifiers");
+
+// Sets membership proofs are checked against, keyed by tree id
+pub const MERKLE_TREES: Map<&str, MerkleTree> = Map::new("merkle_trees");
+
+// Roots each Merkle tree accepts, keyed by tree id and root as 32 little endian bytes. Kept in step
+// with MerkleTree::roots, which orders them
+pub const MERKLE_ROOTS: Map<(&str, &[u8]), ()> = Map::new("merkle_roots");
+
+// Most roots a Merkle tree keeps accepting, the latest included
+pub const MAX_ROOT_HISTORY: u32 = 100;
+
+// Semaphore groups, keyed by group id in decimal
+pub const GROUPS: Map<&str, Group> = Map::new("groups");
+
+// Nullifier hashes of the signals each group accepted, keyed by group id in decimal and nullifier
+// hash. The value is the height of the block that recorded them.
+pub const SIGNAL_NULLIFIERS: Map<(&str, &[u8]), u64> = Map::new("signal_nullifiers");
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct CircuitInfo {
+    // Version of the most recently registered key. Versions count up from 1
+    pub latest_version: u64,
+    // Version proofs are checked against first, none after it was deprecated or revoked
+    pub active_version: Option<u64>,
+    // How SubmitProof derives the nullifier for this circuit. SubmitProof is refused until a key is
+    // registered with one. Circuits stored before this was optional all have one
+    #[serde(default)]
+    pub nullifier: Option<NullifierSource>,
+    // Fixed by the first key registered. Circuits stored before PLONK support are all Groth16
+    #[serde(default)]
+    pub proof_system: ProofSystem,
+    // Names of the public inputs in circuit order, set with SetInputNames
+    #[serde(default)]
+    pub input_names: Option<InputNames>,
+    // Position of the public input VerifyAndExecute binds its messages to, see msgs_hash. Set with
+    // SetMsgsHashInput, VerifyAndExecute is refused until then
+    #[serde(default)]
+    pub msgs_hash_index: Option<usize>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct ProofStr {
-    pub pi_a: Vec<u8>,
-    pub pi_b: Vec<u8>,
-    pub pi_c: Vec<u8>,
-}
-
-pub fn get_verification_key() -> PreparedVerifyingKey<Bls12> {
-    let verification_key_str = r#"{"alpha_1":[1,15,125,182,194,214,187,59,39,41,185,168,217,237,64,22,149,168,240,60,27,201,60,209,10,184,197,249,201,249,173,24,239,239,7,150,4,180,31,176,206,34,78,89,208,178,3,234,16,128,179,155,175,112,85,150,16,140,242,9,235,195,22,85,249,105,74,55,203,13,72,253,108,144,91,173,111,132,239,26,1,42,155,238,96,88,141,188,175,223,68,207,124,176,179,185],"beta_2":[19,242,66,155,85,17,175,139,133,247,63,90,218,163,168,15,12,94,126,20,245,87,119,233,124,15,220,216,1,156,215,103,136,65,115,8,211,134,46,235,17,166,10,227,169,42,209,92,24,192,114,79,138,129,87,52,251,36,122,61,114,134,82,78,25,124,186,204,116,151,242,156,206,181,63,56,223,5,235,185,64,145,5,107,59,227,178,203,62,4,72,100,91,168,157,84,24,42,57,197,81,17,156,57,84,231,196,13,106,10,38,112,36,17,10,40,92,30,86,42,133,112,132,7,99,87,38,138,44,101,59,249,74,249,57,136,160,250,59,105,243,29,220,141,18,244,64,16,174,170,73,225,146,233,210,156,139,135,64,179,180,52,8,15,212,255,62,161,185,173,103,55,188,142,250,145,50,222,54,51,7,5,254,49,86,176,2,130,200,61,240,93],"gamma_2":[19,224,43,96,82,113,159,96,125,172,211,160,136,39,79,101,89,107,208,208,153,32,182,26,181,218,97,187,220,127,80,73,51,76,241,18,19,148,93,87,229,172,125,5,93,4,43,126,2,74,162,178,240,143,10,145,38,8,5,39,45,197,16,81,198,228,122,212,250,64,59,2,180,81,11,100,122,227,209,119,11,172,3,38,168,5,187,239,212,128,86,200,193,33,189,184,6,6,196,160,46,167,52,204,50,172,210,176,43,194,139,153,203,62,40,126,133,167,99,175,38,116,146,171,87,46,153,171,63,55,13,39,92,236,29,161,170,169,7,95,240,95,121,190,12,229,213,39,114,125,110,17,140,201,205,198,218,46,53,26,173,253,155,170,140,189,211,167,109,66,154,105,81,96,209,44,146,58,201,204,59,172,162,137,225,147,84,134,8,184,40,1],"delta_2":[12,100,203,106,241,155,89,207,240,32,103,85,204,197,194,89,236,140,37,204,118,111,204,46,26,44,69,231,21,219,20,102,244,76,177,68,14,234,102,212,251,250,248,101,103,79,246,177,4,22,116,11,192,166,220,221,201,38,163,127,95,206,249,85,249,32,67,57,181,93,121,18,74,121,228,128,55,124,156,54,133,242,193,186,181,18,63,203,214,137,102,197,252,171,185,10,6,111,24,252,154,101,65,249,76,236,22,57,176,129,9,56,241,70,46,253,192,98,57,240,12,245,22,88,178,57,94,188,84,181,145,210,193,232,183,50,54,116,145,205,54,253,244,100,12,63,1,136,66,21,37,50,39,245,169,214,230,251,90,124,192,185,78,65,146,172,16,155,197,242,31,37,2,140,182,141,203,99,205,88,140,239,176,191,136,50,76,181,36,100,10,38],"ic":[[25,130,226,19,123,128,241,61,98,49,149,190,132,254,121,57,25,40,172,200,84,231,203,47,34,188,170,135,142,0,106,157,241,28,236,108,191,42,209,234,86,148,123,86,220,112,20,150,16,161,170,190,217,103,89,115,82,168,129,43,152,171,220,117,85,149,106,196,208,163,118,148,158,132,131,79,74,73,168,76,15,209,176,104,85,177,196,41,137,220,80,215,202,164,183,230],[2,46,19,189,33,253,118,197,231,75,146,201,191,130,15,84,45,216,210,111,132,162,30,90,239,243,42,220,63,214,184,43,177,213,136,53,21,47,74,102,5,95,133,237,140,187,82,20,5,234,156,212,250,184,215,251,0,137,26,8,81,242,131,54,22,223,183,100,41,137,48,18,17,160,138,238,15,22,228,244,206,2,205,183,0,66,230,62,36,140,241,183,34,78,189,250]]}"#;
-    let v_key = verification_key_str.to_string();
-    let vk: VkeyStr = serde_json::from_str(&v_key).unwrap();
-    let vk_alpha_1 = vk.alpha_1;
-    let vk_beta_2 = vk.beta_2;
-    let vk_gamma_2 = vk.gamma_2;
-    let vk_delta_2 = vk.delta_2;
-    let vk_ic = vk.ic;
-    
-    let mut alpha1: [u8; 96] = [0; 96];
-    let mut beta2: [u8; 192] = [0; 192];
-    let mut gamma2: [u8; 192] = [0; 192];
-    let mut delta2: [u8; 192] = [0; 192];
-    let mut ic_0: [u8; 96] = [0; 96];
-    let mut ic_1: [u8; 96] = [0; 96];
-    let mut ic = Vec::new();
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MerkleTree {
+    // Circuit proving membership, with the root among its public inputs
+    pub circuit_id: String,
+    // Position of the root in the circuit's public inputs
+    pub root_index: usize,
+    // Curve of the circuit's key when the tree was created, roots are field elements of it
+    pub curve: Curve,
+    // Can publish roots besides the admin
+    pub owner: Option<Addr>,
+    // How many of the latest roots are accepted, so proofs made just before an update still verify
+    pub history_size: u32,
+    // Accepted roots as 32 little endian bytes, oldest first
+    pub roots: Vec<Binary>,
+}
 
-    for i in 0..vk_alpha_1.len() {
-        alpha1[i] = vk_alpha_1[i];
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Group {
+    // BN254 Groth16 circuit of the Semaphore proofs, compiled for this depth
+    pub circuit_id: String,
+    // Adds members
+    pub admin: Addr,
+    pub depth: u8,
+    // How many of the latest roots signals are accepted against
+    pub history_size: u32,
+    pub num_members: u64,
+    // Last left node written at each level, see semaphore::insert
+    pub filled_subtrees: Vec<Binary>,
+    // Root of an empty subtree at each level, leaves first
+    pub zeroes: Vec<Binary>,
+    // Accepted roots as 32 little endian bytes, oldest first
+    pub roots: Vec<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KeyRegistration {
+    // Hex encoded SHA-256 of the key, see verifying_key_digest
+    pub key_hash: String,
+    pub curve: Curve,
+    pub num_public_inputs: u32,
+    pub registered_height: u64,
+    pub registrant: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyStatus {
+    Active,
+    // Still accepted for proofs made before a rotation, until the deadline if there is one
+    Deprecated { until: Option<Deadline> },
+    // Never accepted again
+    Revoked,
+}
+
+impl KeyStatus {
+    pub fn is_usable(&self, block: &BlockInfo) -> bool {
+        match self {
+            KeyStatus::Active => true,
+            KeyStatus::Deprecated { until: None } => true,
+            KeyStatus::Deprecated { until: Some(deadline) } => !deadline.has_passed(block),
+            KeyStatus::Revoked => false,
+        }
     }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Deadline {
+    Height(u64),
+    Time(Timestamp),
+}
 
-    for i in 0..vk_beta_2.len() {
-        beta2[i] = vk_beta_2[i];
+impl Deadline {
+    pub fn has_passed(&self, block: &BlockInfo) -> bool {
+        match self {
+            Deadline::Height(height) => block.height >= *height,
+            Deadline::Time(time) => block.time >= *time,
+        }
     }
+}
 
-    for i in 0..vk_gamma_2.len() {
-        gamma2[i] = vk_gamma_2[i];
+// How long a deprecated key is still accepted, counted from the block that deprecates it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GracePeriod {
+    Blocks(u64),
+    Seconds(u64),
+}
+
+impl GracePeriod {
+    // How long a key replaced by a newer one stays accepted, unless the registration says otherwise
+    pub const ROTATION_DEFAULT: GracePeriod = GracePeriod::Seconds(7 * 24 * 60 * 60);
+
+    // Fails rather than wrap around for periods past the largest height or time, which would be a
+    // deadline that has already passed
+    pub fn deadline(&self, block: &BlockInfo) -> Result<Deadline, ContractError> {
+        let deadline = match self {
+            GracePeriod::Blocks(blocks) => block.height.checked_add(*blocks).map(Deadline::Height),
+            GracePeriod::Seconds(seconds) => seconds
+                .checked_mul(1_000_000_000)
+                .and_then(|nanos| block.time.nanos().checked_add(nanos))
+                .map(|nanos| Deadline::Time(Timestamp::from_nanos(nanos))),
+        };
+        deadline.ok_or(ContractError::GracePeriodTooLong {})
     }
+}
 
-    for i in 0..vk_delta_2.len() {
-        delta2[i] = vk_delta_2[i];
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NullifierSource {
+    // SHA-256 of the proof points. Not safe against replay: anyone holding a Groth16 proof can
+    // re-randomize it into a new valid proof of the same statement, which hashes differently. This
+    // only stops the very same proof from being submitted twice.
+    ProofHash,
+    // The public input at this position, typically a nullifier the circuit derives from a secret.
+    // Stops any second proof for the same statement.
+    PublicInput { index: usize },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InputNames {
+    Template(CircuitTemplate),
+    // One unique name per public input, in circuit order
+    Custom(Vec<String>),
+}
+
+impl InputNames {
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            InputNames::Template(template) => template.input_names(),
+            InputNames::Custom(names) => names.clone(),
+        }
+    }
+}
+
+// Credential circuits whose public inputs the contract knows by name. Their verifying keys come
+// from each deployment's own trusted setup and are registered with RegisterTemplateKey. The circom
+// sources are in testdata/templates/. Both prove facts about a private 64 bit value committed to as
+// Poseidon(value, salt), e.g. an age or balance attested to by an issuer.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitTemplate {
+    // min <= value <= max
+    Range,
+    // value >= threshold. "value > x" is threshold x + 1
+    Comparison,
+}
+
+impl CircuitTemplate {
+    pub const ALL: [CircuitTemplate; 2] = [CircuitTemplate::Range, CircuitTemplate::Comparison];
+
+    pub fn input_names(&self) -> Vec<String> {
+        let names: &[&str] = match self {
+            CircuitTemplate::Range => &["commitment", "min", "max"],
+            CircuitTemplate::Comparison => &["commitment", "threshold"],
+        };
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CircuitTemplate::Range => "range",
+            CircuitTemplate::Comparison => "comparison",
+        }
+    }
+
+    // circomlib's Poseidon is defined over BN254, so the sources only compile to BN254 keys
+    pub fn curve(&self) -> Curve {
+        Curve::Bn254
     }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofSystem {
+    #[default]
+    Groth16,
+    // KZG based, see plonk.rs
+    Plonk,
+}
+
+// Pairing-friendly curve a verifying key, and every proof checked against it, is defined over
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    #[default]
+    Bls12_381,
+    // alt_bn128, the curve behind the Ethereum pairing precompiles
+    Bn254,
+}
+
+// Uncompressed BLS12-381 points are 96 (G1) or 192 (G2) bytes, compressed ones 48 or 96
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PointEncoding {
+    // Clients predating the encoding field always sent uncompressed points
+    #[default]
+    Uncompressed,
+    Compressed,
+}
+
+// Point bytes are accepted either as base64 or, as older clients send them, as a JSON array of integers
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PointBytes {
+    Base64(Binary),
+    Array(Vec<u8>),
+}
+
+impl From<PointBytes> for Binary {
+    fn from(bytes: PointBytes) -> Self {
+        match bytes {
+            PointBytes::Base64(binary) => binary,
+            PointBytes::Array(array) => Binary::new(array),
+        }
+    }
+}
+
+fn deserialize_point<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Binary, D::Error> {
+    PointBytes::deserialize(deserializer).map(Binary::from)
+}
+
+fn deserialize_points<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Binary>, D::Error> {
+    let points = Vec::<PointBytes>::deserialize(deserializer)?;
+    Ok(points.into_iter().map(Binary::from).collect())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VkeyStr {
+    #[serde(default)]
+    pub curve: Curve,
+    #[serde(default)]
+    pub encoding: PointEncoding,
+    #[serde(deserialize_with = "deserialize_point")]
+    pub alpha_1: Binary,
+    #[serde(deserialize_with = "deserialize_point")]
+    pub beta_2: Binary,
+    #[serde(deserialize_with = "deserialize_point")]
+    pub gamma_2: Binary,
+    #[serde(deserialize_with = "deserialize_point")]
+    pub delta_2: Binary,
+    #[serde(deserialize_with = "deserialize_points")]
+    pub ic: Vec<Binary>,
+}
+
+// A scalar of the key's curve, which must be below that scalar field's modulus
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PublicInput {
+    // Base 10 integer, e.g. "12"
+    Decimal(String),
+    // 32 bytes, least significant byte first, optional 0x prefix
+    HexLe(String),
+    // 32 bytes, most significant byte first, optional 0x prefix
+    HexBe(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProofStr {
+    #[serde(default)]
+    pub encoding: PointEncoding,
+    #[serde(deserialize_with = "deserialize_point")]
+    pub pi_a: Binary,
+    #[serde(deserialize_with = "deserialize_point")]
+    pub pi_b: Binary,
+    #[serde(deserialize_with = "deserialize_point")]
+    pub pi_c: Binary,
+}
 
-    for i in 0..vk_ic[0].len() {
-        ic_0[i] = vk_ic[0][i];
+// SHA-256 over the curve name as it appears in VkeyStr, then alpha_1, beta_2, gamma_2, delta_2 and
+// every ic point, uncompressed. It does not depend on how the key was encoded when registered.
+pub fn verifying_key_digest(vk: &VerifyingKey<Bls12>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"bls12_381");
+    hasher.update(vk.alpha_g1.to_uncompressed());
+    hasher.update(vk.beta_g2.to_uncompressed());
+    hasher.update(vk.gamma_g2.to_uncompressed());
+    hasher.update(vk.delta_g2.to_uncompressed());
+    for point in vk.ic.iter() {
+        hasher.update(point.to_uncompressed());
     }
+    hasher.finalize().into()
+}
+
+pub fn parse_verification_key(vk: &VkeyStr) -> Result<VerifyingKey<Bls12>, ContractError> {
+    let alpha1_affine = parse_g1("alpha_1", &vk.alpha_1, vk.encoding)?;
+    let beta2_affine = parse_g2("beta_2", &vk.beta_2, vk.encoding)?;
+    let gamma2_affine = parse_g2("gamma_2", &vk.gamma_2, vk.encoding)?;
+    let delta2_affine = parse_g2("delta_2", &vk.delta_2, vk.encoding)?;
 
-    for i in 0..vk_ic[1].len() {
-        ic_1[i] = vk_ic[1][i];
+    // A circuit always has the constant "one" input, so ic can never be empty
+    if vk.ic.is_empty() {
+        return Err(ContractError::InvalidVerifyingKey {});
+    }
+    let mut ic = Vec::new();
+    for (i, point) in vk.ic.iter().enumerate() {
+        ic.push(parse_g1(&format!("ic[{}]", i), point, vk.encoding)?);
     }
-    let alpha1_affine = G1Affine::from_uncompressed(&alpha1).unwrap();
-    let beta2_affine = G2Affine::from_uncompressed(&beta2).unwrap();
-    let gamma2_affine = G2Affine::from_uncompressed(&gamma2).unwrap();
-    let delta2_affine = G2Affine::from_uncompressed(&delta2).unwrap();
-    let ic0_affine = G1Affine::from_uncompressed(&ic_0).unwrap();
-    let ic1_affine = G1Affine::from_uncompressed(&ic_1).unwrap();
-    ic.push(ic0_affine);
-    ic.push(ic1_affine);
 
-    let vkk: VerifyingKey<Bls12> = VerifyingKey{
+    Ok(VerifyingKey {
         alpha_g1: alpha1_affine,
         beta_g1: G1Affine::identity(),
         beta_g2: beta2_affine,
@@ -91,40 +401,313 @@ pub fn get_verification_key() -> PreparedVerifyingKey<Bls12> {
         delta_g1: G1Affine::identity(),
         delta_g2: delta2_affine,
         ic,
+    })
+}
+
+// Decodes a proof for a key on the given curve, either a ProofStr or a snarkjs proof.json
+pub fn decode_proof(proof: &str, curve: Curve) -> Result<ProofStr, ContractError> {
+    let value: serde_json::Value = serde_json::from_str(proof).map_err(invalid_proof_json)?;
+
+    // Only snarkjs proofs carry a protocol field
+    if value.get("protocol").is_some() {
+        let snarkjs_proof: SnarkjsProof = serde_json::from_value(value).map_err(invalid_proof_json)?;
+        snarkjs_proof.to_proof_str(curve)
+    } else {
+        serde_json::from_value(value).map_err(invalid_proof_json)
+    }
+}
+
+pub fn parse_proof(pof: &ProofStr) -> Result<Proof<Bls12>, ContractError> {
+    Ok(Proof {
+        a: parse_g1("pi_a", &pof.pi_a, pof.encoding)?,
+        b: parse_g2("pi_b", &pof.pi_b, pof.encoding)?,
+        c: parse_g1("pi_c", &pof.pi_c, pof.encoding)?,
+    })
+}
+
+fn invalid_proof_json(err: serde_json::Error) -> ContractError {
+    ContractError::InvalidProofJson { msg: err.to_string() }
+}
+
+fn parse_g1(field: &str, bytes: &[u8], encoding: PointEncoding) -> Result<G1Affine, ContractError> {
+    let point: Option<G1Affine> = match encoding {
+        PointEncoding::Uncompressed => G1Affine::from_uncompressed_unchecked(&fixed_bytes::<96>(field, bytes)?).into(),
+        // Fails when x has no matching y, i.e. the point is not on the curve
+        PointEncoding::Compressed => G1Affine::from_compressed_unchecked(&fixed_bytes::<48>(field, bytes)?).into(),
     };
-    prepare_verifying_key(&vkk)
+    let point = point.ok_or_else(|| ContractError::InvalidPointEncoding { field: field.to_string() })?;
+    check_point(field, point.is_on_curve().into(), point.is_torsion_free().into())?;
+    Ok(point)
+}
+
+fn parse_g2(field: &str, bytes: &[u8], encoding: PointEncoding) -> Result<G2Affine, ContractError> {
+    let point: Option<G2Affine> = match encoding {
+        PointEncoding::Uncompressed => G2Affine::from_uncompressed_unchecked(&fixed_bytes::<192>(field, bytes)?).into(),
+        PointEncoding::Compressed => G2Affine::from_compressed_unchecked(&fixed_bytes::<96>(field, bytes)?).into(),
+    };
+    let point = point.ok_or_else(|| ContractError::InvalidPointEncoding { field: field.to_string() })?;
+    check_point(field, point.is_on_curve().into(), point.is_torsion_free().into())?;
+    Ok(point)
+}
+
+pub(crate) fn fixed_bytes<const N: usize>(field: &str, bytes: &[u8]) -> Result<[u8; N], ContractError> {
+    bytes.try_into().map_err(|_| ContractError::InvalidPointLength {
+        field: field.to_string(),
+        expected: N,
+        actual: bytes.len(),
+    })
+}
+
+// The same checks from_uncompressed does, split up so the caller learns which one failed
+pub(crate) fn check_point(field: &str, on_curve: bool, torsion_free: bool) -> Result<(), ContractError> {
+    if !on_curve {
+        return Err(ContractError::PointNotOnCurve { field: field.to_string() });
+    }
+    if !torsion_free {
+        return Err(ContractError::PointNotInSubgroup { field: field.to_string() });
+    }
+    Ok(())
+}
+
+// bls12_381 cannot serialize Gt or G2Prepared, so unlike bellman's PreparedVerifyingKey this does
+// not hold e(alpha, beta). Its pair joins the Miller loop instead, which is far cheaper than the
+// full pairing bellman would run every time the key is prepared.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreparedVerifyingKey {
+    neg_alpha_g1: G1Affine,
+    beta_g2: G2Affine,
+    neg_gamma_g2: G2Affine,
+    neg_delta_g2: G2Affine,
+    ic: Vec<G1Affine>,
 }
 
-pub fn parse_proof(proof: String) -> Proof<Bls12> {
-    let pof: ProofStr = serde_json::from_str(&proof).unwrap();
-    let pi_a = pof.pi_a;
-    let pi_b = pof.pi_b;
-    let pi_c = pof.pi_c;
-    let mut a_arr: [u8; 96] = [0; 96];
-    let mut b_arr: [u8; 192] = [0; 192];
-    let mut c_arr: [u8; 96] = [0; 96];
-    for i in 0..pi_a.len() {
-        a_arr[i] = pi_a[i];
+impl PreparedVerifyingKey {
+    pub fn new(vk: &VerifyingKey<Bls12>) -> Self {
+        PreparedVerifyingKey {
+            neg_alpha_g1: -vk.alpha_g1,
+            beta_g2: vk.beta_g2,
+            neg_gamma_g2: -vk.gamma_g2,
+            neg_delta_g2: -vk.delta_g2,
+            ic: vk.ic.clone(),
+        }
     }
 
-    for i in 0..pi_b.len() {
-        b_arr[i] = pi_b[i];
+    // Uncompressed points back to back: alpha, beta, gamma, delta, then every ic point
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.neg_alpha_g1.to_uncompressed());
+        bytes.extend_from_slice(&self.beta_g2.to_uncompressed());
+        bytes.extend_from_slice(&self.neg_gamma_g2.to_uncompressed());
+        bytes.extend_from_slice(&self.neg_delta_g2.to_uncompressed());
+        for point in self.ic.iter() {
+            bytes.extend_from_slice(&point.to_uncompressed());
+        }
+        bytes
     }
 
-    for i in 0..pi_c.len() {
-        c_arr[i] = pi_c[i];
+    // The points were checked when the key was registered, so they are read back without the
+    // subgroup checks
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ContractError> {
+        const G2_START: usize = 96;
+        const IC_START: usize = G2_START + 3 * 192;
+        if bytes.len() <= IC_START || !bytes[IC_START..].chunks_exact(96).remainder().is_empty() {
+            return Err(ContractError::InvalidVerifyingKey {});
+        }
+
+        let mut g2_points = Vec::new();
+        for chunk in bytes[G2_START..IC_START].chunks_exact(192) {
+            g2_points.push(stored_g2(chunk)?);
+        }
+        let mut ic = Vec::new();
+        for chunk in bytes[IC_START..].chunks_exact(96) {
+            ic.push(stored_g1(chunk)?);
+        }
+
+        Ok(PreparedVerifyingKey {
+            neg_alpha_g1: stored_g1(&bytes[..G2_START])?,
+            beta_g2: g2_points[0],
+            neg_gamma_g2: g2_points[1],
+            neg_delta_g2: g2_points[2],
+            ic,
+        })
     }
-    let pia_affine = G1Affine::from_uncompressed(&a_arr).unwrap();
-    let pib_affine = G2Affine::from_uncompressed(&b_arr).unwrap();
-    let pic_affine = G1Affine::from_uncompressed(&c_arr).unwrap();
-    let proof: Proof<Bls12> = Proof {
-        a: pia_affine,
-        b: pib_affine,
-        c: pic_affine,
-    };
-    proof
+
+    pub fn verifying_key(&self) -> VerifyingKey<Bls12> {
+        VerifyingKey {
+            alpha_g1: -self.neg_alpha_g1,
+            beta_g1: G1Affine::identity(),
+            beta_g2: self.beta_g2,
+            gamma_g2: -self.neg_gamma_g2,
+            delta_g1: G1Affine::identity(),
+            delta_g2: -self.neg_delta_g2,
+            ic: self.ic.clone(),
+        }
+    }
+}
+
+fn stored_g1(bytes: &[u8]) -> Result<G1Affine, ContractError> {
+    let bytes = bytes.try_into().map_err(|_| ContractError::InvalidVerifyingKey {})?;
+    Option::from(G1Affine::from_uncompressed_unchecked(bytes)).ok_or(ContractError::InvalidVerifyingKey {})
+}
+
+fn stored_g2(bytes: &[u8]) -> Result<G2Affine, ContractError> {
+    let bytes = bytes.try_into().map_err(|_| ContractError::InvalidVerifyingKey {})?;
+    Option::from(G2Affine::from_uncompressed_unchecked(bytes)).ok_or(ContractError::InvalidVerifyingKey {})
+}
+
+// The Groth16 check e(A, B) = e(alpha, beta) * e(ic(inputs), gamma) * e(C, delta), with everything
+// moved to the left so a single final exponentiation suffices
+pub fn verify_proof<B: PairingBackend>(
+    backend: &B,
+    pvk: &PreparedVerifyingKey,
+    proof: &Proof<Bls12>,
+    public_signal: &[Scalar],
+) -> Result<bool, ContractError> {
+    if public_signal.len() + 1 != pvk.ic.len() {
+        return Ok(false);
+    }
+
+    let mut terms = vec![G1Projective::from(pvk.ic[0])];
+    for (input, point) in public_signal.iter().zip(pvk.ic.iter().skip(1)) {
+        terms.push(point * input);
+    }
+    let mut affine_terms = vec![G1Affine::identity(); terms.len()];
+    G1Projective::batch_normalize(&terms, &mut affine_terms);
+    let acc = backend.aggregate_g1(&affine_terms)?;
+
+    backend.pairing_check(&[
+        (proof.a, proof.b),
+        (pvk.neg_alpha_g1, pvk.beta_g2),
+        (acc, pvk.neg_gamma_g2),
+        (proof.c, pvk.neg_delta_g2),
+    ])
+}
+
+// Runs one randomized batch check first: every proof's equation is weighted by a random scalar
+// and all of them go through a single pairing check. Only when that fails is each proof checked
+// on its own, to tell which ones are invalid.
+pub fn batch_verify_proofs<B: PairingBackend>(
+    backend: &B,
+    pvk: &PreparedVerifyingKey,
+    items: &[(Proof<Bls12>, Vec<Scalar>)],
+) -> Result<Vec<bool>, ContractError> {
+    if items.iter().all(|(_, public_signal)| public_signal.len() + 1 == pvk.ic.len())
+        && batch_check(backend, pvk, items)?
+    {
+        return Ok(vec![true; items.len()]);
+    }
+
+    items
+        .iter()
+        .map(|(proof, public_signal)| verify_proof(backend, pvk, proof, public_signal))
+        .collect()
+}
+
+// The product of e(r_i * A_i, B_i), e(sum(r_i) * -alpha, beta), e(sum(r_i * ic(inputs_i)), -gamma)
+// and e(sum(r_i * C_i), -delta) is the identity for valid proofs
+fn batch_check<B: PairingBackend>(
+    backend: &B,
+    pvk: &PreparedVerifyingKey,
+    items: &[(Proof<Bls12>, Vec<Scalar>)],
+) -> Result<bool, ContractError> {
+    let mut rng = ChaCha20Rng::from_seed(batch_seed(items));
+    let mut weight_sum = Scalar::ZERO;
+    let mut input_weights = vec![Scalar::ZERO; pvk.ic.len() - 1];
+    let mut acc_c = G1Projective::identity();
+    let mut pairs = Vec::new();
+    for (proof, public_signal) in items.iter() {
+        let weight = Scalar::random(&mut rng);
+        weight_sum += weight;
+        for (input_weight, input) in input_weights.iter_mut().zip(public_signal.iter()) {
+            *input_weight += weight * input;
+        }
+        acc_c += proof.c * weight;
+        pairs.push(((proof.a * weight).into(), proof.b));
+    }
+
+    let mut acc_ic = pvk.ic[0] * weight_sum;
+    for (input_weight, point) in input_weights.iter().zip(pvk.ic.iter().skip(1)) {
+        acc_ic += point * input_weight;
+    }
+    pairs.push(((pvk.neg_alpha_g1 * weight_sum).into(), pvk.beta_g2));
+    pairs.push((acc_ic.into(), pvk.neg_gamma_g2));
+    pairs.push((acc_c.into(), pvk.neg_delta_g2));
+    backend.pairing_check(&pairs)
+}
+
+// Queries have no source of randomness, so the batch weights are derived from the proofs
+// themselves. Forging a batch would then mean grinding proofs against a 255 bit challenge.
+fn batch_seed(items: &[(Proof<Bls12>, Vec<Scalar>)]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for (proof, public_signal) in items.iter() {
+        hasher.update(proof.a.to_uncompressed());
+        hasher.update(proof.b.to_uncompressed());
+        hasher.update(proof.c.to_uncompressed());
+        for input in public_signal.iter() {
+            hasher.update(input.to_repr());
+        }
+    }
+    hasher.finalize().into()
+}
+
+pub fn get_public_signal(values: &[PublicInput]) -> Result<Vec<Scalar>, ContractError> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| parse_public_input(value).ok_or(ContractError::InvalidPublicInput { index }))
+        .collect()
+}
+
+fn parse_public_input(value: &PublicInput) -> Option<Scalar> {
+    // from_repr rejects non-canonical encodings, i.e. values >= the field modulus
+    Scalar::from_repr(public_input_le_bytes(value)?).into()
+}
+
+// The public input VerifyAndExecute requires for msgs, as 32 big-endian bytes: SHA-256 of their
+// JSON as the contract serializes it, shifted right by 8 bits so it is below the scalar field
+// modulus of both curves
+pub fn msgs_hash(msgs: &[CosmosMsg]) -> StdResult<[u8; 32]> {
+    let digest = Sha256::digest(to_json_vec(msgs)?);
+    let mut hash = [0u8; 32];
+    hash[1..].copy_from_slice(&digest[..31]);
+    Ok(hash)
+}
+
+// Little-endian bytes of a public input, not yet reduced to any scalar field
+pub(crate) fn public_input_le_bytes(value: &PublicInput) -> Option<[u8; 32]> {
+    match value {
+        PublicInput::Decimal(digits) => decimal_to_le_bytes(digits),
+        PublicInput::HexLe(encoded) => decode_hex_32(encoded),
+        PublicInput::HexBe(encoded) => {
+            let mut bytes = decode_hex_32(encoded)?;
+            bytes.reverse();
+            Some(bytes)
+        }
+    }
+}
+
+pub(crate) fn decimal_to_le_bytes<const N: usize>(digits: &str) -> Option<[u8; N]> {
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut bytes = [0u8; N];
+    for ch in digits.chars() {
+        let mut carry = ch.to_digit(10)?;
+        for byte in bytes.iter_mut() {
+            let value = *byte as u32 * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        // Does not fit in N bytes
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(bytes)
 }
 
-pub fn get_public_signal(value: u64) -> Vec<Scalar> {
-    vec![Scalar::from(value)]
+fn decode_hex_32(encoded: &str) -> Option<[u8; 32]> {
+    let encoded = encoded.strip_prefix("0x").unwrap_or(encoded);
+    hex::decode(encoded).ok()?.try_into().ok()
 }
@@ -0,0 +1,13 @@
+pragma circom 2.0.0;
+
+// Fixtures: multiplier2_*.json hold a BLS12-381 Groth16 key and a proof for a = 3, b = 11.
+// plonk/generate.sh builds a BN254 PLONK key and proof for the same inputs
+template Multiplier2() {
+    signal input a;
+    signal input b;
+    signal output c;
+
+    c <== a * b;
+}
+
+component main = Multiplier2();
@@ -0,0 +1,27 @@
+#!/bin/sh
+# Writes the snarkjs PLONK fixture test_snarkjs_plonk_fixture reads: verification_key.json,
+# proof.json and public.json for testdata/multiplier2.circom over BN254, with a = 3 and b = 11.
+# Needs circom 2.1 and snarkjs 0.7 (npm install -g snarkjs@0.7).
+set -eu
+
+cd "$(dirname "$0")"
+build=$(mktemp -d)
+trap 'rm -rf "$build"' EXIT
+
+circom ../multiplier2.circom --r1cs --wasm -o "$build"
+
+# A throwaway powers of tau ceremony, big enough for the circuit
+snarkjs powersoftau new bn128 8 "$build/pot_0.ptau"
+snarkjs powersoftau contribute "$build/pot_0.ptau" "$build/pot_1.ptau" --name=fixture -e="$(head -c 32 /dev/urandom | od -An -tx1 | tr -d ' \n')"
+snarkjs powersoftau prepare phase2 "$build/pot_1.ptau" "$build/pot_final.ptau"
+
+snarkjs plonk setup "$build/multiplier2.r1cs" "$build/pot_final.ptau" "$build/multiplier2.zkey"
+snarkjs zkey export verificationkey "$build/multiplier2.zkey" verification_key.json
+
+echo '{"a": "3", "b": "11"}' > "$build/input.json"
+snarkjs wtns calculate "$build/multiplier2_js/multiplier2.wasm" "$build/input.json" "$build/witness.wtns"
+snarkjs plonk prove "$build/multiplier2.zkey" "$build/witness.wtns" proof.json public.json
+snarkjs plonk verify verification_key.json public.json proof.json
+
+# The contract has to accept what snarkjs accepted
+(cd ../.. && cargo test --lib snarkjs_plonk_fixture -- --include-ignored)
@@ -0,0 +1,24 @@
+pragma circom 2.0.0;
+
+include "circomlib/circuits/poseidon.circom";
+include "circomlib/circuits/bitify.circom";
+
+// CircuitTemplate::Comparison: value >= threshold for the value committed to. Public signals, in
+// declaration order: commitment, threshold
+template Comparison() {
+    signal input commitment;
+    signal input threshold;
+    signal input value;
+    signal input salt;
+
+    component hash = Poseidon(2);
+    hash.inputs[0] <== value;
+    hash.inputs[1] <== salt;
+    commitment === hash.out;
+
+    // A difference that wrapped around the field does not fit in 64 bits
+    component above_threshold = Num2Bits(64);
+    above_threshold.in <== value - threshold;
+}
+
+component main {public [commitment, threshold]} = Comparison();
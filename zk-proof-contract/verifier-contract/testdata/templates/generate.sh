@@ -0,0 +1,40 @@
+#!/bin/sh
+# Writes the snarkjs Groth16 fixtures test_snarkjs_template_fixtures reads: <template>_verification_key.json,
+# <template>_proof.json and <template>_public.json for range.circom and comparison.circom over BN254.
+# Both commit to value 30 with salt 987654321; range proves 18 <= 30 <= 65 and comparison 30 >= 18.
+# Needs circom 2.1, snarkjs 0.7 (npm install -g snarkjs@0.7) and npm for circomlib.
+set -eu
+
+cd "$(dirname "$0")"
+build=$(mktemp -d)
+trap 'rm -rf "$build"' EXIT
+
+(cd "$build" && npm install --silent circomlib@2.0.5 circomlibjs@0.1.7)
+commitment=$(cd "$build" && node -e '
+  require("circomlibjs").buildPoseidon().then((poseidon) => {
+    console.log(poseidon.F.toString(poseidon([30, 987654321])));
+  });
+')
+
+# A throwaway powers of tau ceremony, big enough for both circuits
+snarkjs powersoftau new bn128 10 "$build/pot_0.ptau"
+snarkjs powersoftau contribute "$build/pot_0.ptau" "$build/pot_1.ptau" --name=fixture -e="$(head -c 32 /dev/urandom | od -An -tx1 | tr -d ' \n')"
+snarkjs powersoftau prepare phase2 "$build/pot_1.ptau" "$build/pot_final.ptau"
+
+echo "{\"commitment\": \"$commitment\", \"min\": \"18\", \"max\": \"65\", \"value\": \"30\", \"salt\": \"987654321\"}" > "$build/range_input.json"
+echo "{\"commitment\": \"$commitment\", \"threshold\": \"18\", \"value\": \"30\", \"salt\": \"987654321\"}" > "$build/comparison_input.json"
+
+for template in range comparison; do
+    circom "$template.circom" --r1cs --wasm -l "$build/node_modules" -o "$build"
+
+    snarkjs groth16 setup "$build/$template.r1cs" "$build/pot_final.ptau" "$build/${template}_0.zkey"
+    snarkjs zkey contribute "$build/${template}_0.zkey" "$build/$template.zkey" --name=fixture -e="$(head -c 32 /dev/urandom | od -An -tx1 | tr -d ' \n')"
+    snarkjs zkey export verificationkey "$build/$template.zkey" "${template}_verification_key.json"
+
+    snarkjs wtns calculate "$build/${template}_js/$template.wasm" "$build/${template}_input.json" "$build/$template.wtns"
+    snarkjs groth16 prove "$build/$template.zkey" "$build/$template.wtns" "${template}_proof.json" "${template}_public.json"
+    snarkjs groth16 verify "${template}_verification_key.json" "${template}_public.json" "${template}_proof.json"
+done
+
+# The contract has to accept what snarkjs accepted
+(cd ../.. && cargo test --lib snarkjs_template_fixtures -- --include-ignored)
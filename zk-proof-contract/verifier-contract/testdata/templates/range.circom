@@ -0,0 +1,27 @@
+pragma circom 2.0.0;
+
+include "circomlib/circuits/poseidon.circom";
+include "circomlib/circuits/bitify.circom";
+
+// CircuitTemplate::Range: min <= value <= max for the value committed to. Public signals, in
+// declaration order: commitment, min, max
+template Range() {
+    signal input commitment;
+    signal input min;
+    signal input max;
+    signal input value;
+    signal input salt;
+
+    component hash = Poseidon(2);
+    hash.inputs[0] <== value;
+    hash.inputs[1] <== salt;
+    commitment === hash.out;
+
+    // Differences that wrapped around the field do not fit in 64 bits
+    component above_min = Num2Bits(64);
+    above_min.in <== value - min;
+    component below_max = Num2Bits(64);
+    below_max.in <== max - value;
+}
+
+component main {public [commitment, min, max]} = Range();
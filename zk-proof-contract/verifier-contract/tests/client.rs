@@ -0,0 +1,45 @@
+// Drives the contract with messages built by verifier-client, the way an off-chain prover would.
+// Lives outside src/ because verifier-client depends on this crate: the unit tests in
+// src/contract/tests would see their own copy of the message types.
+
+use bls12_381::Scalar;
+use cosmwasm_std::testing::{message_info, mock_dependencies, mock_env};
+use cosmwasm_std::from_json;
+use rand_core::OsRng;
+
+use verifier_client::Multiplier;
+use verifier_contract::contract::{execute, instantiate, query};
+use verifier_contract::msg::{InstantiateMsg, VerifyProofResponse};
+use verifier_contract::state::PointEncoding;
+
+#[test]
+fn test_client_proofs_verify() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let admin = deps.api.addr_make("addr1");
+    let msg = InstantiateMsg {
+        admin_address: admin.to_string()
+    };
+    let _ = instantiate(deps.as_mut(), env.clone(), message_info(&admin, &[]), msg).unwrap();
+
+    let params = verifier_client::setup(2, &mut OsRng).unwrap();
+    let pairs = [(Scalar::from(3), Scalar::from(4)), (-Scalar::from(5), Scalar::from(1u64 << 40))];
+    let products = Multiplier::products(&pairs);
+
+    for (circuit_id, encoding) in [("uncompressed", PointEncoding::Uncompressed), ("compressed", PointEncoding::Compressed)] {
+        let register_msg = verifier_client::register_verifying_key_msg(circuit_id, &params.vk, encoding, None);
+        let _ = execute(deps.as_mut(), env.clone(), message_info(&admin, &[]), register_msg).unwrap();
+
+        let proof = verifier_client::prove(&params, &pairs, &mut OsRng).unwrap();
+        let query_msg = verifier_client::verify_proof_msg(circuit_id, &proof, &products, encoding).unwrap();
+        let response: VerifyProofResponse = from_json(query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
+        assert!(response.valid);
+        assert_eq!(response.public_inputs, verifier_client::public_inputs(&products));
+
+        // Same proof, products swapped
+        let swapped = [products[1], products[0]];
+        let query_msg = verifier_client::verify_proof_msg(circuit_id, &proof, &swapped, encoding).unwrap();
+        let response: VerifyProofResponse = from_json(query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
+        assert!(!response.valid);
+    }
+}